@@ -0,0 +1,8 @@
+#!/bin/sh
+# Debug and release builds have to simulate exactly the same thing, so a run recorded
+# in one can be reproduced in the other. Both check the state hash after 10k ticks of
+# the demo board against the golden value in test_determinism.
+set -e
+cd "$(dirname "$0")/.."
+cargo test -- determinism
+cargo test --release -- determinism
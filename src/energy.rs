@@ -0,0 +1,43 @@
+use crate::shape::Point;
+
+// All energies are per unit mass, with distances in pixels and time in seconds.
+
+pub fn kinetic(velocity: Point<f32>) -> f32 {
+    0.5 * velocity.length_squared()
+}
+
+// Potential energy relative to the plane through `reference` perpendicular to gravity.
+// Anything on the far side of the plane (in the direction of gravity) is negative.
+pub fn potential(pos: Point<f32>, gravity: Point<f32>, reference: Point<f32>) -> f32 {
+    -gravity.dot(pos - reference)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{energy, shape::Point};
+
+    #[test]
+    fn test_kinetic() {
+        assert_eq!(energy::kinetic(Point::new(3.0, 4.0)), 12.5);
+        assert_eq!(energy::kinetic(Point::zero()), 0.0);
+    }
+
+    #[test]
+    fn test_potential() {
+        let gravity = Point::new(0.0, 10.0);
+        let reference = Point::new(0.0, 100.0);
+
+        assert_eq!(
+            energy::potential(Point::new(50.0, 100.0), gravity, reference),
+            0.0
+        );
+        assert_eq!(
+            energy::potential(Point::new(0.0, 80.0), gravity, reference),
+            200.0
+        );
+        assert_eq!(
+            energy::potential(Point::new(0.0, 120.0), gravity, reference),
+            -200.0
+        );
+    }
+}
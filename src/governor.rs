@@ -0,0 +1,143 @@
+use std::{collections::VecDeque, time::Duration};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mitigation {
+    CapSpawning,
+    UncapSpawning,
+    CullBalls,
+}
+
+// Watches how long update ticks take and decides when the frontend should shed load.
+// Every observation is assumed to correspond to one tick, so "for a second" means one
+// second worth of ticks rather than wall clock time.
+pub struct LoadGovernor {
+    budget: Duration,
+    samples: VecDeque<Duration>,
+    level: u8,
+    over_for: Duration,
+    under_for: Duration,
+}
+
+impl LoadGovernor {
+    const WINDOW: usize = 32;
+    const OVERLOADED: f32 = 0.8;
+    const RECOVERED: f32 = 0.5;
+    const PATIENCE: Duration = Duration::from_secs(1);
+    const MAX_LEVEL: u8 = 2;
+
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            samples: VecDeque::with_capacity(Self::WINDOW),
+            level: 0,
+            over_for: Duration::ZERO,
+            under_for: Duration::ZERO,
+        }
+    }
+
+    pub fn is_mitigating(&self) -> bool {
+        self.level > 0
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    pub fn observe(&mut self, duration: Duration) -> Vec<Mitigation> {
+        if self.samples.len() == Self::WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration);
+
+        let load = self.average().as_secs_f32() / self.budget.as_secs_f32();
+        let mut out = Vec::new();
+
+        // Between the two thresholds nothing changes, which keeps the governor from
+        // flapping when the load hovers around the limit
+        if load > Self::OVERLOADED {
+            self.under_for = Duration::ZERO;
+            self.over_for += self.budget;
+            if self.over_for >= Self::PATIENCE {
+                self.over_for = Duration::ZERO;
+                self.level = (self.level + 1).min(Self::MAX_LEVEL);
+                out.push(match self.level {
+                    1 => Mitigation::CapSpawning,
+                    _ => Mitigation::CullBalls,
+                });
+            }
+        } else if load < Self::RECOVERED {
+            self.over_for = Duration::ZERO;
+            if self.level > 0 {
+                self.under_for += self.budget;
+                if self.under_for >= Self::PATIENCE {
+                    self.under_for = Duration::ZERO;
+                    self.level -= 1;
+                    if self.level == 0 {
+                        out.push(Mitigation::UncapSpawning);
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::governor::{LoadGovernor, Mitigation};
+
+    const BUDGET: Duration = Duration::from_millis(10);
+
+    fn run(governor: &mut LoadGovernor, duration: Duration, ticks: usize) -> Vec<Mitigation> {
+        (0..ticks)
+            .flat_map(|_| governor.observe(duration))
+            .collect()
+    }
+
+    #[test]
+    fn test_escalation() {
+        let mut governor = LoadGovernor::new(BUDGET);
+
+        // Under a second of overload is tolerated
+        assert!(run(&mut governor, Duration::from_millis(9), 90).is_empty());
+        assert!(!governor.is_mitigating());
+
+        assert_eq!(
+            run(&mut governor, Duration::from_millis(9), 10),
+            vec![Mitigation::CapSpawning]
+        );
+        assert_eq!(
+            run(&mut governor, Duration::from_millis(9), 100),
+            vec![Mitigation::CullBalls]
+        );
+        // Culling keeps happening for as long as the load stays high
+        assert_eq!(
+            run(&mut governor, Duration::from_millis(9), 100),
+            vec![Mitigation::CullBalls]
+        );
+    }
+
+    #[test]
+    fn test_recovery_hysteresis() {
+        let mut governor = LoadGovernor::new(BUDGET);
+        run(&mut governor, Duration::from_millis(9), 200);
+        assert!(governor.is_mitigating());
+
+        // Load in between the thresholds neither escalates nor recovers
+        assert!(run(&mut governor, Duration::from_millis(7), 500).is_empty());
+        assert!(governor.is_mitigating());
+
+        let recovered = run(&mut governor, Duration::from_millis(2), 300);
+        assert_eq!(recovered, vec![Mitigation::UncapSpawning]);
+        assert!(!governor.is_mitigating());
+
+        // A short spike after recovering doesn't immediately escalate again
+        assert!(run(&mut governor, Duration::from_millis(9), 50).is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+use crate::shape::Point;
+
+// Turns a quick flick of the pointer into a launch velocity. Motion events are buffered
+// with their timestamps and the pointer velocity is estimated with a least squares fit
+// over the last few milliseconds before release.
+pub struct FlickTracker {
+    samples: VecDeque<(u32, Point<f32>)>,
+}
+
+impl FlickTracker {
+    const WINDOW_MS: u32 = 80;
+    // Pointer speeds in px/s below this don't fire at all
+    const MIN_SPEED: f32 = 400.0;
+    const SCALE: f32 = 0.5;
+    const MAX_SPEED: f32 = 1500.0;
+
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn begin(&mut self, timestamp: u32, pos: Point<f32>) {
+        self.samples.clear();
+        self.samples.push_back((timestamp, pos));
+    }
+
+    pub fn motion(&mut self, timestamp: u32, pos: Point<f32>) {
+        self.samples.push_back((timestamp, pos));
+        self.forget_before(timestamp.saturating_sub(Self::WINDOW_MS));
+    }
+
+    pub fn release(&mut self, timestamp: u32, pos: Point<f32>) -> Option<Point<f32>> {
+        self.motion(timestamp, pos);
+        let velocity = Self::estimate_velocity(self.samples.make_contiguous())?;
+        self.samples.clear();
+
+        let speed = velocity.length();
+        if speed < Self::MIN_SPEED {
+            return None;
+        }
+        Some(velocity.with_length((speed * Self::SCALE).min(Self::MAX_SPEED)))
+    }
+
+    fn forget_before(&mut self, timestamp: u32) {
+        while self.samples.front().is_some_and(|(t, _)| *t < timestamp) {
+            self.samples.pop_front();
+        }
+    }
+
+    // Slope of the least squares line through the samples, in px/s
+    fn estimate_velocity(samples: &[(u32, Point<f32>)]) -> Option<Point<f32>> {
+        let (first, _) = *samples.first()?;
+        let n = samples.len() as f32;
+        let time = |t: u32| (t - first) as f32 / 1000.0;
+
+        let mean_t = samples.iter().map(|(t, _)| time(*t)).sum::<f32>() / n;
+        let mean_p = samples.iter().fold(Point::zero(), |acc, (_, p)| acc + *p) / n;
+
+        let mut covariance = Point::zero();
+        let mut variance = 0.0;
+        for (t, p) in samples {
+            let dt = time(*t) - mean_t;
+            covariance += (*p - mean_p) * dt;
+            variance += dt * dt;
+        }
+
+        if variance <= f32::EPSILON {
+            return None;
+        }
+        Some(covariance / variance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{input::FlickTracker, shape::Point};
+
+    #[test]
+    fn test_flick() {
+        let mut tracker = FlickTracker::new();
+        tracker.begin(1000, Point::new(100.0, 100.0));
+        for i in 1..=10 {
+            // 2 px per ms to the right, 1 px per ms down
+            tracker.motion(
+                1000 + i * 10,
+                Point::new(100.0 + i as f32 * 20.0, 100.0 + i as f32 * 10.0),
+            );
+        }
+        let velocity = tracker
+            .release(1105, Point::new(310.0, 205.0))
+            .expect("fast flick should fire");
+
+        assert!((velocity.x - 1000.0).abs() < 1.0);
+        assert!((velocity.y - 500.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_flick_is_clamped() {
+        let mut tracker = FlickTracker::new();
+        tracker.begin(0, Point::zero());
+        tracker.motion(10, Point::new(200.0, 0.0));
+        let velocity = tracker.release(20, Point::new(400.0, 0.0)).unwrap();
+
+        assert!((velocity.length() - FlickTracker::MAX_SPEED).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_slow_drag_then_stop_does_not_fire() {
+        let mut tracker = FlickTracker::new();
+        tracker.begin(0, Point::new(100.0, 100.0));
+        for i in 1..=50 {
+            tracker.motion(i * 10, Point::new(100.0 + i as f32, 100.0));
+        }
+
+        // Holding still for a while before letting go
+        assert!(tracker.release(800, Point::new(150.0, 100.0)).is_none());
+    }
+
+    #[test]
+    fn test_tap_does_not_fire() {
+        let mut tracker = FlickTracker::new();
+        tracker.begin(0, Point::new(100.0, 100.0));
+        assert!(tracker.release(30, Point::new(100.0, 100.0)).is_none());
+    }
+}
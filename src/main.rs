@@ -1,11 +1,17 @@
 use poggle::Poggle;
 
+mod energy;
+mod governor;
+mod input;
+mod palette;
 mod poggle;
+mod powerup;
 mod sdl;
 mod shape;
+mod spatial;
 
 fn main() {
-    let mut poggle = Poggle::new();
+    let mut poggle = Poggle::default();
 
     sdl::run(&mut poggle);
 }
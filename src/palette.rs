@@ -0,0 +1,62 @@
+use sdl2::pixels::Color;
+
+const COLD: Color = Color::RGB(40, 90, 255);
+const WARM: Color = Color::WHITE;
+const HOT: Color = Color::RGB(255, 140, 0);
+
+pub fn lerp(from: Color, to: Color, t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color::RGBA(
+        channel(from.r, to.r),
+        channel(from.g, to.g),
+        channel(from.b, to.b),
+        channel(from.a, to.a),
+    )
+}
+
+// Blue when still, white at half of `max_speed` and orange from `max_speed` onwards
+pub fn heat(speed: f32, max_speed: f32) -> Color {
+    let t = if max_speed > 0.0 {
+        (speed / max_speed).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+
+    if t < 0.5 {
+        lerp(COLD, WARM, t * 2.0)
+    } else {
+        lerp(WARM, HOT, t * 2.0 - 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::Color;
+
+    use crate::palette::{self, COLD, HOT, WARM};
+
+    #[test]
+    fn test_lerp() {
+        let black = Color::RGB(0, 0, 0);
+        assert_eq!(palette::lerp(black, Color::WHITE, 0.0), black);
+        assert_eq!(palette::lerp(black, Color::WHITE, 1.0), Color::WHITE);
+        assert_eq!(
+            palette::lerp(black, Color::WHITE, 0.5),
+            Color::RGB(128, 128, 128)
+        );
+        assert_eq!(palette::lerp(black, Color::WHITE, 2.0), Color::WHITE);
+    }
+
+    #[test]
+    fn test_heat() {
+        assert_eq!(palette::heat(0.0, 1000.0), COLD);
+        assert_eq!(palette::heat(500.0, 1000.0), WARM);
+        assert_eq!(palette::heat(1000.0, 1000.0), HOT);
+
+        // Out of range speeds stick to the ends of the gradient
+        assert_eq!(palette::heat(-10.0, 1000.0), COLD);
+        assert_eq!(palette::heat(5000.0, 1000.0), HOT);
+        assert_eq!(palette::heat(10.0, 0.0), HOT);
+    }
+}
@@ -1,18 +1,114 @@
-use std::{f32::consts, time::Duration};
+use std::{collections::BTreeMap, f32::consts, time::Duration};
 
 use sdl2::pixels::Color;
 
 use crate::{
-    sdl::{self, Render, draw_circle, draw_circle_filled},
-    shape::{Body, Point, PolarPoint, Region, Shape, solve_quadratic},
+    energy, palette,
+    powerup::ActivePowerups,
+    sdl::{
+        self, Render, draw_capsule, draw_capsule_filled, draw_circle, draw_circle_filled,
+        draw_polygon, draw_polygon_filled,
+    },
+    shape::{Body, Point, PolarPoint, Region, Shape, sweep_circle_circle, sweep_circle_rect},
+    spatial::SpatialHash,
 };
 
-const GRAVITY: Point<f32> = Point::new(0.0, 550.0);
-
 pub struct Poggle {
     balls: Vec<Ball>,
     pegs: Vec<Peg>,
+    // Has to be rebuilt whenever pegs are added, removed or moved
+    peg_grid: SpatialHash,
     tick: u64,
+    next_handle: u64,
+    // Balls below this line are gone. It also serves as the zero level for potential energy.
+    kill_plane: f32,
+    ball_coloring: BallColoring,
+    powerups: ActivePowerups,
+    // Off by default so balls pass through each other like they always have
+    ball_collisions: bool,
+    physics: PhysicsConfig,
+    // How long the simulation has run, which is what moving pegs follow
+    clock: Duration,
+    bucket: Option<Bucket>,
+    // Balls that have landed in the bucket, over the whole game
+    caught: u32,
+    // Left and right. Only there while the Flippers powerup is active.
+    flippers: [Flipper; 2],
+    // Set by shooting and cleared once the last ball is gone, so the shot only ends once
+    shot_in_progress: bool,
+    mirroring: Option<Mirroring>,
+    zones: Vec<ForceZone>,
+    // Indices of the two pegs on each complete portal channel
+    portals: Vec<(usize, usize)>,
+    // Zones are invisible unless debugging
+    show_zones: bool,
+}
+
+// A capsule that swings around `pivot` between its resting angle and its raised one
+pub struct Flipper {
+    pivot: Point<f32>,
+    length: f32,
+    angle: f32,
+    rest: f32,
+    raised: f32,
+    pressed: bool,
+    // How fast it's swinging during the current step, in rad/s clockwise on screen
+    angular_velocity: f32,
+}
+
+// Slides back and forth along the bottom, catching balls that drop into it from above.
+// Its sides are solid.
+pub struct Bucket {
+    // Middle of the opening at the top
+    pos: Point<f32>,
+    path: Motion,
+    // Between the insides of the walls
+    width: f32,
+    depth: f32,
+}
+
+// Pushes every ball with its center inside the region along with a constant acceleration,
+// like wind or an updraft. Where zones overlap they add up.
+pub struct ForceZone {
+    region: Body,
+    acceleration: Point<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BallColoring {
+    Flat,
+    // By speed, from cold to hot
+    Heat,
+    // Debug view of the energy monitor
+    Energy,
+}
+
+impl BallColoring {
+    pub fn next(self) -> Self {
+        match self {
+            BallColoring::Flat => BallColoring::Heat,
+            BallColoring::Heat => BallColoring::Energy,
+            BallColoring::Energy => BallColoring::Flat,
+        }
+    }
+}
+
+// Where the ball's center will be when it touches the other body, with `toi` the
+// fraction of the timestep it takes to get there
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Collision {
+    point: Point<f32>,
+    toi: f32,
+    normal: Point<f32>,
+}
+
+// What a ball bounced off
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Obstacle {
+    Peg(usize),
+    Wall,
+    Bucket,
+    Flipper(usize),
 }
 
 pub struct Target {
@@ -20,30 +116,128 @@ pub struct Target {
     dir: Point<f32>,
 }
 
+// Handles are never reused, so a handle to a despawned ball can't alias a newer one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BallHandle(u64);
+
 pub struct Ball {
     pos: Point<f32>,
     velocity: Point<f32>,
-    start: Point<f32>,
+    initial_energy: f32,
+    energy_budget: f32,
+    handle: Option<BallHandle>,
+    state: BallState,
+    gained_energy: bool,
+    // Angular velocity in rad/s, clockwise on screen, and how far the ball has turned
+    spin: f32,
+    rotation: f32,
+    radius: f32,
+    // Portal channels the ball has just come out of, and the tick each one opens up again
+    portal_cooldowns: Vec<(u8, u64)>,
+}
+
+// The constants the world runs on. The defaults are what the game has always played with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhysicsConfig {
+    pub gravity: Point<f32>,
+    // Used by the walls, by balls hitting each other and by the pegs of the default board
+    pub elasticity: f32,
+    pub ball_radius: f32,
+    pub walls: Walls,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Walls {
+    // The sides of the window bounce balls back in
+    Solid,
+    // Balls can leave through the sides and are gone once they fall past the kill plane
+    Open,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BallState {
+    Free,
+    // Held in the center of a cannon peg and left out of integration until released
+    Captured { by_peg: usize, release_tick: u64 },
 }
 
 impl Ball {
     const RADIUS: f32 = 6.0;
-    const ELASTICITY: f32 = 0.9;
+    // Relative slack allowed before a ball counts as having gained energy
+    const ENERGY_TOLERANCE: f32 = 0.001;
+    const COLOR: Color = Color::RED;
+    // Speed at which heat coloring is as hot as it gets
+    const HEAT_MAX_SPEED: f32 = 1500.0;
+    // Contacts slower than this into the surface don't bounce, in px/s
+    const RESTING_SPEED: f32 = 20.0;
+    // Fraction of its spin a ball loses per second
+    const SPIN_DAMPING: f32 = 0.8;
+    // How sharply a spinning ball curves, in radians of turn per radian of spin
+    const MAGNUS: f32 = 0.01;
+    // Overlaps shallower than this, in px, are just touching
+    const CONTACT_SLOP: f32 = 0.01;
 }
 
+#[derive(Clone)]
 pub struct Peg {
     body: Body,
     is_hit: bool,
     peg_type: PegType,
+    // How much of the ball's speed into the peg it keeps when bouncing off, and how much
+    // of its speed along the surface it loses
+    restitution: f32,
+    friction: f32,
+    motion: Option<Motion>,
+    // In rad/s, clockwise on screen. Only polygons look any different for it.
+    angular_velocity: f32,
+    // Only bumpers keep track of it, to flash for a bit after being hit
+    last_hit_tick: Option<u64>,
+    // Gone once this runs out. Only bounces count, not balls resting on it or going
+    // through a portal or a cannon.
+    hits_remaining: u8,
+}
+
+// A path for a peg to ride along, repeating every `period` seconds. Linear motion eases
+// in and out at the ends instead of snapping back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    Linear {
+        from: Point<f32>,
+        to: Point<f32>,
+        period: f32,
+    },
+    #[cfg_attr(
+        not(test),
+        allow(dead_code, reason = "no board has circling pegs yet, only the tests")
+    )]
+    Circular {
+        center: Point<f32>,
+        radius: f32,
+        period: f32,
+    },
 }
 
+#[derive(Clone, Copy)]
 pub enum PegType {
     Standard,
     Target,
     PointBoost,
     PowerUp(PowerUp),
+    // Catches the ball and fires it along `direction` (radians) at `speed`. Only the
+    // first hit in a shot is captured, after that it's just a solid peg.
+    Cannon { direction: f32, speed: f32 },
+    // Pulls balls within `range` px of its center towards it with an acceleration of
+    // `strength` over the distance squared. It's still solid like any other peg.
+    Magnet { strength: f32, range: f32 },
+    // Kicks the ball back out `boost` px/s faster on top of the bounce. Lights up like any
+    // other peg, but it's never something a level needs cleared.
+    Bumper { boost: f32 },
+    // Sends the ball out of the other portal on the same channel, still going the same
+    // way. A channel needs exactly two of them, otherwise they're just solid pegs.
+    Portal { channel: u8 },
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PowerUp {
     SuperGuide,
     MultiBall,
@@ -55,6 +249,103 @@ pub enum PowerUp {
     Fireball,
     FlowerPower,
     Zen,
+    // Flips the board left to right once the shot is over
+    MirrorWorld,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            gravity: Point::new(0.0, 550.0),
+            elasticity: 0.9,
+            ball_radius: Ball::RADIUS,
+            walls: Walls::Solid,
+        }
+    }
+}
+
+// Pegs sliding over to where the board flip put them. They're drawn somewhere on the way
+// from `from` and can't be hit until they get there.
+struct Mirroring {
+    from: Vec<Point<f32>>,
+    elapsed: Duration,
+}
+
+// What peg reachability depends on besides the peg, so rendering can work it out once a
+// frame instead of once per peg
+struct Reach {
+    unbounded: bool,
+    // Smallest y of any ball, so the one furthest up
+    highest_ball: f32,
+    // The most any ball could end up with, nothing if there are no balls
+    energy: f32,
+}
+
+impl Motion {
+    // In radians per second, a full cycle being one turn
+    fn rate(&self) -> f32 {
+        let (Motion::Linear { period, .. } | Motion::Circular { period, .. }) = *self;
+        consts::TAU / period
+    }
+
+    fn position_at(&self, time: f32) -> Point<f32> {
+        let phase = self.rate() * time;
+        match *self {
+            Motion::Linear { from, to, .. } => from + (to - from) * ((1.0 - phase.cos()) / 2.0),
+            Motion::Circular { center, radius, .. } => {
+                center + Point::from(PolarPoint::new(phase, radius))
+            }
+        }
+    }
+
+    fn velocity_at(&self, time: f32) -> Point<f32> {
+        let rate = self.rate();
+        let phase = rate * time;
+        match *self {
+            Motion::Linear { from, to, .. } => (to - from) * (phase.sin() * rate / 2.0),
+            Motion::Circular { radius, .. } => {
+                Point::from(PolarPoint::new(phase + consts::FRAC_PI_2, radius * rate))
+            }
+        }
+    }
+
+    // The furthest down the path goes
+    fn lowest(&self) -> f32 {
+        match *self {
+            Motion::Linear { from, to, .. } => from.y.max(to.y),
+            Motion::Circular { center, radius, .. } => center.y + radius.abs(),
+        }
+    }
+
+    // The furthest up the path goes
+    fn highest(&self) -> f32 {
+        match *self {
+            Motion::Linear { from, to, .. } => from.y.min(to.y),
+            Motion::Circular { center, radius, .. } => center.y - radius.abs(),
+        }
+    }
+
+    // The same path flipped around the vertical line at x = `axis`. A negative radius and
+    // period go around the mirrored circle the other way, starting from the other side.
+    fn mirrored(self, axis: f32) -> Motion {
+        let flip = |p: Point<f32>| Point::new(2.0 * axis - p.x, p.y);
+        match self {
+            Motion::Linear { from, to, period } => Motion::Linear {
+                from: flip(from),
+                to: flip(to),
+                period,
+            },
+            Motion::Circular {
+                center,
+                radius,
+                period,
+            } => Motion::Circular {
+                center: flip(center),
+                radius: -radius,
+                period: -period,
+            },
+        }
+    }
 }
 
 impl Ball {
@@ -62,108 +353,562 @@ impl Ball {
         Self {
             pos,
             velocity,
-            start: pos,
+            initial_energy: 0.0,
+            energy_budget: 0.0,
+            handle: None,
+            state: BallState::Free,
+            gained_energy: false,
+            spin: 0.0,
+            rotation: 0.0,
+            radius: Ball::RADIUS,
+            portal_cooldowns: Vec::new(),
         }
     }
 
-    fn will_collide(&self, other: &Body, time: Duration) -> Option<Point<f32>> {
-        match &other.shape {
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "API for embedding the simulation, the game doesn't need it"
+        )
+    )]
+    pub fn pos(&self) -> Point<f32> {
+        self.pos
+    }
+
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "API for embedding the simulation, the game doesn't need it"
+        )
+    )]
+    pub fn velocity(&self) -> Point<f32> {
+        self.velocity
+    }
+
+    #[cfg(test)]
+    pub fn state(&self) -> BallState {
+        self.state
+    }
+
+    // Moves the ball without going through the physics, counting the change in
+    // energy as legitimate
+    fn place(&mut self, pos: Point<f32>, velocity: Point<f32>, gravity: Point<f32>) {
+        self.add_energy_budget(
+            energy::kinetic(velocity) - energy::kinetic(self.velocity)
+                + energy::potential(pos, gravity, self.pos),
+        );
+        self.pos = pos;
+        self.velocity = velocity;
+    }
+
+    // Pegs on a path are treated as moving in a straight line at `other_velocity` for the
+    // timestep. Everything is worked out relative to the peg, and only the point of
+    // impact is moved along to where the peg has got to by then.
+    fn will_collide(
+        &self,
+        other: &Body,
+        other_velocity: Point<f32>,
+        time: Duration,
+    ) -> Option<Collision> {
+        let velocity = self.velocity - other_velocity;
+        let collision = match &other.shape {
             Shape::Circle { radius } => {
-                let movement = self.velocity * time.as_secs_f32();
+                let (toi, point) = sweep_circle_circle(
+                    self.pos,
+                    velocity,
+                    self.radius,
+                    other.pos,
+                    *radius,
+                    time.as_secs_f32(),
+                )?;
+                Some(Collision {
+                    point,
+                    toi,
+                    normal: other.normal_at(point),
+                })
+            }
+            Shape::Rect {
+                half_extents,
+                rounding,
+            } => {
+                let (toi, point) = sweep_circle_rect(
+                    self.pos,
+                    velocity,
+                    self.radius,
+                    other.pos,
+                    *half_extents,
+                    *rounding,
+                    time.as_secs_f32(),
+                )?;
+                Some(Collision {
+                    point,
+                    toi,
+                    normal: other.normal_at(point),
+                })
+            }
+            // A capsule is a polygon with just the two ends for corners
+            Shape::Polygon { rounding, .. }
+            | Shape::Capsule {
+                radius: rounding, ..
+            } => {
+                let movement = velocity * time.as_secs_f32();
+                let r = self.radius + rounding;
 
                 // Check if collision is even possible during this timestep
                 if self.pos.distance_to_squared(other.pos)
-                    > (radius + Ball::RADIUS + movement.length()).powi(2)
+                    > (other.bounding_radius() + r + movement.length()).powi(2)
                 {
                     return None;
                 }
 
-                // With line -> y = mx + k and circle -> (x - p)^2 + (y - q)^2 = r^2 we get
-                // Ax^2 + Bx + C = 0 where A = m^2 + 1, B = 2(mk - mq - p), and
-                // C = q^2 - r^2 + p^2 - 2kq + k^2. Solutions are then given by
-                // x' = (-B ± sqrt(B^2 - 4AC)) / 2A.
-                let m = movement.y / movement.x;
-                let k = self.pos.y - self.pos.x * m;
-
-                let p = other.pos.x;
-                let q = other.pos.y;
-                let r = radius + Ball::RADIUS;
-
-                if movement.x.abs() < 1.0 {
-                    // In this case we have x = t which gives us
-                    // y^2 - 2qy + (p^2 + q^2 - r^2 - 2dp + d^2)
-                    let d = self.pos.x;
-                    let a = 1.0;
-                    let b = -2.0 * q;
-                    let c = p.powi(2) - r.powi(2) + q.powi(2) - 2.0 * d * p + d.powi(2);
-
-                    let (y1, y2) = solve_quadratic(a, b, c)?;
-                    let y_new = if (y1 - self.pos.y).abs() < (y2 - self.pos.y).abs() {
-                        y1
-                    } else {
-                        y2
-                    };
-
-                    if movement.is_longer_than(165.0 * time.as_secs_f32())
-                        && self.velocity.y.signum() != (y_new - self.pos.y).signum()
-                    {
-                        return None;
-                    }
-
-                    return Some(Point::new(self.pos.x, y_new));
+                // Already overlapping, which only counts if the ball is moving further in
+                if other.extend(self.radius).contains(self.pos) {
+                    let normal = other.normal_at(self.pos);
+                    return (normal.dot(movement) < 0.0).then_some(Collision {
+                        point: self.pos,
+                        toi: 0.0,
+                        normal,
+                    });
                 }
 
-                let x_new = {
-                    let a = m.powi(2) + 1.0;
-                    let b = 2.0 * (m * k - m * q - p);
-                    let c = q.powi(2) - r.powi(2) + p.powi(2) - 2.0 * k * q + k.powi(2);
-
-                    // Find the closest of the two points
-                    let (x1, x2) = solve_quadratic(a, b, c)?;
-                    if (x1 - self.pos.x).abs() < (x2 - self.pos.x).abs() {
-                        x1
-                    } else {
-                        x2
+                // The region the ball's center can't enter is the polygon grown by the
+                // ball's radius: every edge pushed out along its normal, joined by arcs
+                // around the corners. The earliest time the center crosses any of those
+                // is the collision. Everything is solved in terms of the fraction `t` of
+                // the movement, so vertical movement needs no special casing.
+                let mut earliest: Option<f32> = None;
+                let mut consider = |t: f32| {
+                    if (0.0..=1.0).contains(&t) && earliest.is_none_or(|e| t < e) {
+                        earliest = Some(t);
                     }
                 };
 
-                // Check the direction is correct
-                if movement.is_longer_than(165.0 * time.as_secs_f32())
-                    && movement.x.signum() != (x_new - self.pos.x).signum()
-                {
-                    return None;
+                for (a, b, n) in &other.edges() {
+                    let approach = n.dot(movement);
+                    if approach >= 0.0 {
+                        continue;
+                    }
+
+                    let t = (r - n.dot(a.to(self.pos))) / approach;
+                    let contact = self.pos + movement * t - *n * r;
+                    let along = a.to(contact).dot(a.to(*b));
+                    if (0.0..=a.to(*b).length_squared()).contains(&along) {
+                        consider(t);
+                    }
                 }
 
-                // As y = mx + k
-                let collision = Point::new(x_new, m * x_new + k);
+                for vertex in other.vertices() {
+                    if let Some((t, _)) = sweep_circle_circle(
+                        self.pos,
+                        velocity,
+                        self.radius,
+                        vertex,
+                        *rounding,
+                        time.as_secs_f32(),
+                    ) {
+                        consider(t);
+                    }
+                }
 
-                // Check if collision will happen during the allotted time
-                if self.pos.distance_to_squared(collision) * 0.99 > movement.length_squared() {
-                    return None;
+                earliest.map(|toi| {
+                    let point = self.pos + movement * toi;
+                    Collision {
+                        point,
+                        toi,
+                        normal: other.normal_at(point),
+                    }
+                })
+            }
+        }?;
+        Some(Collision {
+            point: collision.point + other_velocity * (collision.toi * time.as_secs_f32()),
+            ..collision
+        })
+    }
+
+    // Both balls have the same mass, so each is pushed away by half the overlap and they
+    // swap the parts of their velocities along the line between them. The energy that
+    // goes from one ball to the other is legitimate, so it goes through `place`.
+    fn collide_with(&mut self, other: &mut Ball, elasticity: f32, gravity: Point<f32>) {
+        let offset = self.pos.to(other.pos);
+        let distance = offset.length();
+        let reach = self.radius + other.radius;
+        if distance >= reach {
+            return;
+        }
+        let normal = if distance > f32::EPSILON {
+            offset / distance
+        } else {
+            Point::new(1.0, 0.0)
+        };
+
+        let push = normal * ((reach - distance) / 2.0);
+        let approach = (other.velocity - self.velocity).dot(normal);
+        let impulse = if approach < 0.0 {
+            normal * (-(1.0 + elasticity) * approach / 2.0)
+        } else {
+            Point::zero()
+        };
+
+        let (pos, velocity) = (self.pos - push, self.velocity - impulse);
+        self.place(pos, velocity, gravity);
+        let (pos, velocity) = (other.pos + push, other.velocity + impulse);
+        other.place(pos, velocity, gravity);
+    }
+
+    // Splits the velocity into the part going into the surface and the part going along
+    // it. The first is turned around and scaled by `restitution`, the second is reduced
+    // by `friction`. Nothing happens if the ball is already moving away.
+    fn bounce(&mut self, normal: Point<f32>, restitution: f32, friction: f32) {
+        let into = normal.dot(self.velocity);
+        if into >= 0.0 {
+            return;
+        }
+        let along = self.velocity - normal * into;
+        self.velocity = along * (1.0 - friction) - normal * into * restitution;
+
+        // Friction grabs the ball and starts it rolling along the surface
+        let rolling = (normal.x * along.y - normal.y * along.x) / self.radius;
+        self.spin += (rolling - self.spin) * friction;
+    }
+
+    // The curve turns the velocity without changing its length, so spin can never add
+    // energy to the ball
+    fn apply_spin(&mut self, d: f32) {
+        self.velocity = self.velocity.rotated(self.spin * Ball::MAGNUS * d);
+        self.rotation += self.spin * d;
+        self.spin *= (1.0 - Ball::SPIN_DAMPING).powf(d);
+    }
+
+    // Moves the ball out of any pegs it's overlapping and stops it from moving further in
+    fn depenetrate<'a>(
+        &mut self,
+        pegs: impl Iterator<Item = &'a Peg> + Clone,
+        gravity: Point<f32>,
+    ) {
+        // Being pushed out onto a surface leaves it just touching, which is fine
+        let overlaps = |pos: Point<f32>| {
+            pegs.clone().any(|peg| {
+                peg.body
+                    .extend(self.radius - Ball::CONTACT_SLOP)
+                    .contains(pos)
+            })
+        };
+
+        // Each peg pushes the ball straight out of itself, and all the pushes are added up
+        let mut correction = Point::zero();
+        let mut overlapping = false;
+        for peg in pegs.clone() {
+            let extended = peg.body.extend(self.radius);
+            if !extended.contains(self.pos) {
+                continue;
+            }
+            overlapping = true;
+            correction += self.pos.to(extended.closest_surface_point(self.pos));
+
+            let normal = peg.body.normal_at(self.pos);
+            self.velocity -= normal * normal.dot(self.velocity).min(0.0);
+        }
+        if !overlapping {
+            return;
+        }
+        self.pos += correction;
+
+        // In a gap too narrow for the ball the pushes from either side cancel out, so
+        // lift it out the top instead of letting it squeeze through
+        if overlaps(self.pos) {
+            let up = -gravity.normalized();
+            let (mut clear, mut stuck) = (4.0 * self.radius, 0.0);
+            for _ in 0..16 {
+                let mid = (clear + stuck) / 2.0;
+                if overlaps(self.pos + up * mid) {
+                    stuck = mid;
+                } else {
+                    clear = mid;
                 }
+            }
+            self.pos += up * clear;
+        }
+    }
+
+    // Corners of the box covering everything the ball touches while moving for `time`,
+    // plus `margin` on all sides
+    fn swept_bounds(&self, time: Duration, margin: f32) -> (Point<f32>, Point<f32>) {
+        let end = self.pos + self.velocity * time.as_secs_f32();
+        let reach = Point::new(self.radius + margin, self.radius + margin);
+        (
+            Point::new(self.pos.x.min(end.x), self.pos.y.min(end.y)) - reach,
+            Point::new(self.pos.x.max(end.x), self.pos.y.max(end.y)) + reach,
+        )
+    }
+
+    // Walls are where the ball's center stops, so the ball itself stays inside the window
+    fn will_hit_wall(&self, time: Duration, walls: Walls) -> Option<Collision> {
+        if walls == Walls::Open {
+            return None;
+        }
+        let movement = self.velocity * time.as_secs_f32();
+        let (wall, normal) = if movement.x < 0.0 {
+            (self.radius, Point::new(1.0, 0.0))
+        } else if movement.x > 0.0 {
+            (
+                sdl::WINDOW_WIDTH as f32 - self.radius,
+                Point::new(-1.0, 0.0),
+            )
+        } else {
+            return None;
+        };
+
+        // Already past the wall (from gravity integration, or spawned there)
+        if (self.pos.x - wall) * normal.x <= 0.0 {
+            return Some(Collision {
+                point: Point::new(wall, self.pos.y),
+                toi: 0.0,
+                normal,
+            });
+        }
+
+        let toi = (wall - self.pos.x) / movement.x;
+        (toi <= 1.0).then_some(Collision {
+            point: self.pos + movement * toi,
+            toi,
+            normal,
+        })
+    }
+
+    // A portal the ball just came out of is an ordinary peg for a few ticks, so the ball
+    // can't bounce between two portals forever
+    fn portal_ready(&mut self, channel: u8, tick: u64) -> bool {
+        self.portal_cooldowns.retain(|&(_, until)| until > tick);
+        self.portal_cooldowns.iter().all(|&(c, _)| c != channel)
+    }
+
+    fn total_energy(&self, reference: Point<f32>, gravity: Point<f32>) -> f32 {
+        energy::kinetic(self.velocity) + energy::potential(self.pos, gravity, reference)
+    }
+
+    // Anything that legitimately adds energy to the ball (or takes it away) has to
+    // report it here, otherwise the energy monitor flags it.
+    pub fn add_energy_budget(&mut self, amount: f32) {
+        self.energy_budget += amount;
+    }
 
-                Some(collision)
+    fn has_gained_energy(&self, reference: Point<f32>, gravity: Point<f32>) -> bool {
+        let allowed = self.initial_energy + self.energy_budget;
+        self.total_energy(reference, gravity)
+            > allowed + allowed.abs().max(1.0) * Ball::ENERGY_TOLERANCE
+    }
+}
+
+impl Bucket {
+    const WALL_THICKNESS: f32 = 8.0;
+    const COLOR: Color = Color::RGB(40, 40, 40);
+
+    pub fn new(path: Motion, width: f32, depth: f32) -> Self {
+        Self {
+            pos: path.position_at(0.0),
+            path,
+            width,
+            depth,
+        }
+    }
+
+    fn walls(&self) -> [Body; 2] {
+        let half_extents = Point::new(Bucket::WALL_THICKNESS / 2.0, self.depth / 2.0);
+        let offset = (self.width + Bucket::WALL_THICKNESS) / 2.0;
+        [-offset, offset].map(|x| Body {
+            pos: self.pos + Point::new(x, self.depth / 2.0),
+            shape: Shape::Rect {
+                half_extents,
+                rounding: 0.0,
+            },
+        })
+    }
+
+    // Only a ball going down through the opening counts, not one skimming across the rim
+    fn catches(&self, from: Point<f32>, to: Point<f32>) -> bool {
+        if !(from.y < self.pos.y && to.y >= self.pos.y) {
+            return false;
+        }
+        let crossing = from.x + (to.x - from.x) * (self.pos.y - from.y) / (to.y - from.y);
+        (crossing - self.pos.x).abs() <= self.width / 2.0
+    }
+
+    // Whether a ball falling freely from where it is could still come down through the
+    // opening. A wall bounce on the way is too hard to call, so that counts as a maybe.
+    fn could_catch(&self, ball: &Ball, physics: &PhysicsConfig, clock: f32) -> bool {
+        let drop = self.pos.y - ball.pos.y;
+        let (g, v) = (physics.gravity, ball.velocity);
+        let discriminant = v.y * v.y + 2.0 * g.y * drop;
+        if drop <= 0.0 || discriminant < 0.0 {
+            return false;
+        }
+        // Time until it's down at the opening, in a form that still works without gravity
+        let t = 2.0 * drop / (v.y + discriminant.sqrt());
+        let x = ball.pos.x + v.x * t + g.x * t * t / 2.0;
+        let inside = ball.radius..=sdl::WINDOW_WIDTH as f32 - ball.radius;
+        if physics.walls == Walls::Solid && !inside.contains(&x) {
+            return true;
+        }
+        // Glancing off the rim can still knock it in
+        let reach = self.width / 2.0 + Bucket::WALL_THICKNESS + ball.radius;
+        (x - self.path.position_at(clock + t).x).abs() <= reach
+    }
+}
+
+impl Render for Bucket {
+    fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
+    where
+        T: sdl2::render::RenderTarget,
+    {
+        canvas.set_draw_color(Bucket::COLOR);
+        for wall in self.walls() {
+            if let Shape::Rect { half_extents, .. } = wall.shape {
+                canvas.fill_rect(sdl::centered_rect(wall.pos, half_extents))?;
+            }
+        }
+        let half_width = self.width / 2.0 + Bucket::WALL_THICKNESS;
+        let floor = self.pos + Point::new(0.0, self.depth);
+        canvas.fill_rect(sdl::centered_rect(
+            floor,
+            Point::new(half_width, Bucket::WALL_THICKNESS / 2.0),
+        ))
+    }
+}
+
+impl ForceZone {
+    const COLOR: Color = Color::RGBA(255, 255, 255, 90);
+
+    #[cfg_attr(
+        not(test),
+        allow(dead_code, reason = "for boards with zones, the demo board has none")
+    )]
+    pub fn new(region: Body, acceleration: Point<f32>) -> Self {
+        Self {
+            region,
+            acceleration,
+        }
+    }
+}
+
+// The outline of the zone, with a line from its middle showing which way it pushes
+impl Render for ForceZone {
+    fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
+    where
+        T: sdl2::render::RenderTarget,
+    {
+        canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+        canvas.set_draw_color(ForceZone::COLOR);
+        let pos = self.region.pos;
+        match &self.region.shape {
+            Shape::Circle { radius } => {
+                draw_circle(canvas, pos.x as u32, pos.y as u32, *radius as u32)?
+            }
+            Shape::Polygon {
+                points, rotation, ..
+            } => draw_polygon(canvas, points, pos, *rotation)?,
+            Shape::Rect { half_extents, .. } => {
+                canvas.draw_rect(sdl::centered_rect(pos, *half_extents))?
             }
-            Shape::Polygon { points, rotation } => todo!(),
+            Shape::Capsule { a, b, radius } => draw_capsule(canvas, pos + *a, pos + *b, *radius)?,
+        }
+        canvas.draw_line(pos, pos + self.acceleration * 0.1)
+    }
+}
+
+impl Flipper {
+    const RADIUS: f32 = 8.0;
+    const SPEED: f32 = 15.0;
+    const COLOR: Color = Color::RGB(200, 120, 0);
+
+    fn new(pivot: Point<f32>, length: f32, rest: f32, raised: f32) -> Self {
+        Self {
+            pivot,
+            length,
+            angle: rest,
+            rest,
+            raised,
+            pressed: false,
+            angular_velocity: 0.0,
+        }
+    }
+
+    fn body(&self) -> Body {
+        Body {
+            pos: self.pivot,
+            shape: Shape::Capsule {
+                a: Point::zero(),
+                b: Point::from(PolarPoint::new(self.angle, self.length)),
+                radius: Flipper::RADIUS,
+            },
         }
     }
 
-    fn potential_energy(&self) -> f32 {
-        (sdl::WINDOW_HEIGHT as f32 - self.pos.y) * GRAVITY.y
+    fn surface_velocity(&self, point: Point<f32>) -> Point<f32> {
+        let arm = self.pivot.to(point);
+        Point::new(-arm.y, arm.x) * self.angular_velocity
+    }
+
+    // Swings towards wherever the input says it should be, without overshooting
+    fn begin_step(&mut self, d: f32) {
+        let target = if self.pressed { self.raised } else { self.rest };
+        self.angular_velocity = ((target - self.angle) / d).clamp(-Flipper::SPEED, Flipper::SPEED);
+    }
+
+    fn end_step(&mut self, d: f32) {
+        self.angle += self.angular_velocity * d;
+    }
+
+    // Speed of the tip at full swing
+    fn max_speed(&self) -> f32 {
+        Flipper::SPEED * (self.length + Flipper::RADIUS)
+    }
+
+    // Anywhere the tip could swing to
+    fn lowest_reach(&self) -> f32 {
+        self.pivot.y + self.length + Flipper::RADIUS
+    }
+}
+
+impl Render for Flipper {
+    fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
+    where
+        T: sdl2::render::RenderTarget,
+    {
+        let tip = self.pivot + Point::from(PolarPoint::new(self.angle, self.length));
+        canvas.set_draw_color(Flipper::COLOR);
+        draw_capsule_filled(canvas, self.pivot, tip, Flipper::RADIUS)?;
+        canvas.set_draw_color(Color::BLACK);
+        draw_capsule(canvas, self.pivot, tip, Flipper::RADIUS)
     }
+}
 
-    fn total_energy(&self) -> f32 {
-        self.velocity.kinetic_energy() + self.potential_energy()
+impl Default for Poggle {
+    fn default() -> Self {
+        Self::new(None)
     }
 }
 
 impl Poggle {
-    pub fn new() -> Self {
+    const CANNON_HOLD_TICKS: u64 = 20;
+    const MAX_COLLISIONS_PER_TICK: usize = 8;
+    // Beyond this balls are allowed to move further per substep rather than stall the game
+    const MAX_SUBSTEPS: u32 = 16;
+    const BALL_RELAXATION_PASSES: usize = 3;
+    const MIRROR_DURATION: Duration = Duration::from_secs(1);
+    const PORTAL_COOLDOWN_TICKS: u64 = 10;
+
+    pub fn new(physics: Option<PhysicsConfig>) -> Self {
+        let physics = physics.unwrap_or_default();
         let spacing = 75.0;
-        let pegs = Self::generate_grid(
+        let pegs: Vec<Peg> = Self::generate_grid(
             Point::new(100.0, 400.0),
             Point::new(sdl::WINDOW_WIDTH as f32 - 100.0, 700.0),
             spacing,
+            physics.elasticity,
+            0.0,
         )
         .into_iter()
         .chain(Self::generate_grid(
@@ -171,6 +916,8 @@ impl Poggle {
             Point::new(sdl::WINDOW_WIDTH as f32 - 100.0, 700.0)
                 - Point::new(spacing / 2.0, spacing / 2.0),
             spacing,
+            physics.elasticity,
+            0.0,
         ))
         .collect();
 
@@ -179,7 +926,6 @@ impl Poggle {
         let center = sdl::WINDOW_WIDTH as f32 / 2.0;
         let positions = (-amount..amount + 1)
             .map(|i| Point::new(center + i as f32 / amount as f32 * space - 15.0, 100.0));
-        let balls = positions.map(|pos| Ball::new(pos, Point::zero())).collect();
 
         // let pegs = vec![Peg {
         //     body: Body {
@@ -193,14 +939,64 @@ impl Poggle {
         //     peg_type: PegType::Standard,
         // }];
 
-        Self {
-            balls,
-            pegs,
+        let bucket_top = sdl::WINDOW_HEIGHT as f32 - 30.0;
+        let mut poggle = Self {
+            balls: Vec::new(),
+            pegs: Vec::new(),
+            peg_grid: SpatialHash::new(std::iter::empty()),
             tick: 0,
+            next_handle: 0,
+            kill_plane: sdl::WINDOW_HEIGHT as f32 + physics.ball_radius,
+            ball_coloring: BallColoring::Flat,
+            powerups: ActivePowerups::new(),
+            ball_collisions: false,
+            physics,
+            clock: Duration::ZERO,
+            flippers: Self::flippers(),
+            bucket: Some(Bucket::new(
+                Motion::Linear {
+                    from: Point::new(150.0, bucket_top),
+                    to: Point::new(sdl::WINDOW_WIDTH as f32 - 150.0, bucket_top),
+                    period: 6.0,
+                },
+                100.0,
+                30.0,
+            )),
+            caught: 0,
+            shot_in_progress: false,
+            mirroring: None,
+            zones: Vec::new(),
+            portals: Vec::new(),
+            show_zones: false,
+        };
+        poggle.add_pegs(pegs);
+        for pos in positions {
+            poggle.shoot(pos, Point::zero());
         }
+        poggle
+    }
+
+    // Down near the bottom corners, pointing in and down while resting
+    fn flippers() -> [Flipper; 2] {
+        let (y, inset, length, swing) = (690.0, 120.0, 160.0, 0.5);
+        [
+            Flipper::new(Point::new(inset, y), length, swing, -swing),
+            Flipper::new(
+                Point::new(sdl::WINDOW_WIDTH as f32 - inset, y),
+                length,
+                consts::PI - swing,
+                consts::PI + swing,
+            ),
+        ]
     }
 
-    fn generate_grid(origin: Point<f32>, end: Point<f32>, spacing: f32) -> Vec<Peg> {
+    fn generate_grid(
+        origin: Point<f32>,
+        end: Point<f32>,
+        spacing: f32,
+        restitution: f32,
+        friction: f32,
+    ) -> Vec<Peg> {
         let mut out = Vec::new();
         let mut point = origin;
         while point.y <= end.y {
@@ -211,6 +1007,12 @@ impl Poggle {
                 },
                 is_hit: false,
                 peg_type: PegType::Standard,
+                restitution,
+                friction,
+                motion: None,
+                angular_velocity: 0.0,
+                last_hit_tick: None,
+                hits_remaining: 1,
             });
 
             point.x += spacing;
@@ -222,98 +1024,934 @@ impl Poggle {
         out
     }
 
-    pub fn shoot(&mut self, origin: Point<f32>, velocity: Point<f32>) {
-        self.balls.push(Ball::new(origin, velocity));
+    pub fn shoot(&mut self, origin: Point<f32>, velocity: Point<f32>) -> BallHandle {
+        let handle = BallHandle(self.next_handle);
+        self.next_handle += 1;
+
+        let mut ball = Ball::new(origin, velocity);
+        ball.handle = Some(handle);
+        ball.radius = self.physics.ball_radius;
+        ball.initial_energy = ball.total_energy(self.energy_reference(), self.physics.gravity);
+        self.balls.push(ball);
+        self.shot_in_progress = true;
+        handle
     }
 
-    pub fn update(&mut self, delta: Duration) {
-        self.balls.retain_mut(|ball| {
-            if ball.pos.y > sdl::WINDOW_HEIGHT as f32 + Ball::RADIUS {
-                return false;
+    // FNV-1a over the exact bits of everything that moves, for checking that two runs
+    // of the simulation did the same thing. The H key prints it, to compare a run in
+    // one build against the same run in another.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |bits: u64| {
+            for byte in bits.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
             }
+        };
 
-            let d = delta.as_secs_f32();
-            ball.velocity += GRAVITY * d;
-            ball.pos += ball.velocity * d;
-
-            for peg in &mut self.pegs {
-                if peg.body.extend(Ball::RADIUS).contains(ball.pos) {
-                    ball.pos = match &peg.body.shape {
-                        Shape::Circle { radius } => {
-                            peg.body.pos
-                                + peg
-                                    .body
-                                    .pos
-                                    .to(ball.pos)
-                                    .with_length(*radius + Ball::RADIUS)
-                        }
-                        Shape::Polygon { points, rotation } => todo!(),
-                    };
-                }
-                if let Some(collision) = ball.will_collide(&peg.body, delta) {
-                    let start_velocity = ball.velocity;
-
-                    let distance_to_travel = ball.velocity.length() * delta.as_secs_f32();
-                    let reflect = peg.body.pos.to(collision).normalized();
-
-                    // this is not entirely correct
-                    ball.velocity += reflect * reflect.dot(ball.velocity).abs() * 2.0;
-                    ball.velocity = ball
-                        .velocity
-                        .with_length(start_velocity.length() * Ball::ELASTICITY);
+        feed(self.tick);
+        for ball in &self.balls {
+            for value in [
+                ball.pos.x,
+                ball.pos.y,
+                ball.velocity.x,
+                ball.velocity.y,
+                ball.spin,
+            ] {
+                feed(value.to_bits() as u64);
+            }
+        }
+        for peg in &self.pegs {
+            feed(peg.is_hit as u64);
+        }
+        hash
+    }
 
-                    ball.pos = collision
-                        + ball.velocity.normalized()
-                            * (distance_to_travel - ball.pos.distance_to(collision));
-                    peg.is_hit = true;
+    #[cfg(test)]
+    pub fn add_peg(&mut self, peg: Peg) {
+        self.add_pegs([peg]);
+    }
 
-                    // if ball.velocity.length_squared() > start_velocity.length_squared() {
-                    //     println!("Tick {}: ball got {:.0}% speed when bouncing off peg at {} (angle {:.2}, {:.2} -> {:.2}, EK {:.0} -> {:.0})",self.tick, ball.velocity.length() / start_velocity.length() * 100.0, peg.body.pos, std::convert::Into::<PolarPoint>::into(peg.body.pos.to(ball.pos)).angle * 180.0 / consts::PI, start_velocity, ball.velocity, start_velocity.kinetic_energy(), ball.velocity.kinetic_energy());
-                    // }
+    pub fn add_pegs(&mut self, pegs: impl IntoIterator<Item = Peg>) {
+        self.pegs.extend(pegs);
+        self.move_pegs();
+        self.rebuild_peg_grid();
+        self.link_portals();
+        if let Err(err) = self.check_portals() {
+            debug_assert!(false, "{err}");
+        }
+    }
 
-                    break;
-                }
-            }
+    // Broken pegs stay where they are until the whole update is done, so nothing in the
+    // middle of it has peg indices change under it. Anything holding one is fixed up here.
+    fn remove_broken_pegs(&mut self) {
+        let broken = |peg: &Peg| peg.hits_remaining == 0;
+        if !self.pegs.iter().any(broken) {
+            return;
+        }
 
-            if ball.pos.x < Ball::RADIUS / 2.0
-                || ball.pos.x > sdl::WINDOW_WIDTH as f32 - Ball::RADIUS / 2.0
+        let mut remaining = 0;
+        let new_index: Vec<Option<usize>> = self
+            .pegs
+            .iter()
+            .map(|peg| {
+                (!broken(peg)).then(|| {
+                    remaining += 1;
+                    remaining - 1
+                })
+            })
+            .collect();
+        for ball in &mut self.balls {
+            if let BallState::Captured {
+                by_peg,
+                release_tick,
+            } = ball.state
             {
-                ball.velocity.x *= -1.0;
+                // The cannon broke with the ball still in it, which just drops it
+                ball.state = match new_index[by_peg] {
+                    Some(by_peg) => BallState::Captured {
+                        by_peg,
+                        release_tick,
+                    },
+                    None => BallState::Free,
+                };
             }
+        }
+        if let Some(mirroring) = &mut self.mirroring {
+            let mut index = new_index.iter();
+            mirroring
+                .from
+                .retain(|_| index.next().is_some_and(Option::is_some));
+        }
 
-            // for peg in &self.pegs {
-            //     if peg.body.extend(Ball::RADIUS).contains(ball.pos) {
-            //         println!("Ball is inside peg at {}", peg.body.pos);
-            //     }
-            // }
+        self.pegs.retain(|peg| !broken(peg));
+        self.rebuild_peg_grid();
+        self.link_portals();
+    }
 
-            true
-        });
+    fn portal_channels(&self) -> BTreeMap<u8, Vec<usize>> {
+        let mut channels: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
+        for (i, peg) in self.pegs.iter().enumerate() {
+            if let PegType::Portal { channel } = peg.peg_type {
+                channels.entry(channel).or_default().push(i);
+            }
+        }
+        channels
+    }
 
-        if self.balls.is_empty() {
-            for peg in &mut self.pegs {
-                peg.is_hit = false;
+    fn link_portals(&mut self) {
+        self.portals = self
+            .portal_channels()
+            .into_values()
+            .filter_map(|pegs| match pegs[..] {
+                [a, b] => Some((a, b)),
+                _ => None,
+            })
+            .collect();
+    }
+
+    // Pegs can only be added with their portals in complete pairs, but breaking one can
+    // still leave its partner on its own
+    pub fn check_portals(&self) -> Result<(), String> {
+        for (channel, pegs) in self.portal_channels() {
+            if pegs.len() != 2 {
+                return Err(format!(
+                    "portal channel {channel} has {} portals, it needs exactly 2",
+                    pegs.len()
+                ));
             }
         }
+        Ok(())
+    }
 
-        self.tick += 1;
+    fn rebuild_peg_grid(&mut self) {
+        self.peg_grid = SpatialHash::new(self.pegs.iter().map(|peg| &peg.body));
     }
-}
 
-impl Render for Poggle {
-    fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
-    where
-        T: sdl2::render::RenderTarget,
-    {
-        for ball in &self.balls {
-            ball.render(canvas)?;
+    // Puts every peg with a path, and the bucket, where it should be by now. Positions
+    // come straight from the clock rather than being stepped, so they don't drift.
+    fn move_pegs(&mut self) {
+        let clock = self.clock.as_secs_f32();
+        if let Some(bucket) = &mut self.bucket {
+            bucket.pos = bucket.path.position_at(clock);
         }
+        let mut moved = false;
+        for peg in &mut self.pegs {
+            if let Some(motion) = &peg.motion {
+                peg.body.pos = motion.position_at(clock);
+                moved = true;
+            }
+        }
+        if moved {
+            self.rebuild_peg_grid();
+        }
+    }
 
-        for peg in &self.pegs {
-            peg.render(canvas)?;
+    fn spin_pegs(&mut self, delta: Duration) {
+        for peg in &mut self.pegs {
+            if let Shape::Polygon { rotation, .. } = &mut peg.body.shape {
+                *rotation += peg.angular_velocity * delta.as_secs_f32();
+            }
         }
+    }
 
-        // canvas.set_draw_color(Color::GREEN);
+    // Fastest any part of any peg is moving
+    fn fastest_peg(&self) -> f32 {
+        let clock = self.clock.as_secs_f32();
+        self.pegs
+            .iter()
+            .map(|peg| {
+                let linear = peg.motion.map_or(Point::zero(), |m| m.velocity_at(clock));
+                linear.length() + peg.angular_velocity.abs() * peg.body.bounding_radius()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    // Fastest anything balls can bounce off is moving
+    fn fastest_obstacle(&self) -> f32 {
+        let clock = self.clock.as_secs_f32();
+        let bucket = self
+            .bucket
+            .as_ref()
+            .map_or(0.0, |b| b.path.velocity_at(clock).length());
+        let flippers = if self.flippers_active() {
+            self.flippers
+                .iter()
+                .map(Flipper::max_speed)
+                .fold(0.0, f32::max)
+        } else {
+            0.0
+        };
+        self.fastest_peg().max(bucket).max(flippers)
+    }
+
+    fn flippers_active(&self) -> bool {
+        self.powerups.is_active(PowerUp::Flippers)
+    }
+
+    pub fn set_flipper_input(&mut self, left: bool, right: bool) {
+        self.flippers[0].pressed = left;
+        self.flippers[1].pressed = right;
+    }
+
+    pub fn physics(&self) -> &PhysicsConfig {
+        &self.physics
+    }
+
+    fn energy_reference(&self) -> Point<f32> {
+        Point::new(0.0, self.kill_plane)
+    }
+
+    pub fn ball_coloring(&self) -> BallColoring {
+        self.ball_coloring
+    }
+
+    pub fn set_ball_coloring(&mut self, coloring: BallColoring) {
+        self.ball_coloring = coloring;
+    }
+
+    #[cfg_attr(
+        not(test),
+        allow(dead_code, reason = "for boards with zones, the demo board has none")
+    )]
+    pub fn add_zone(&mut self, zone: ForceZone) {
+        self.zones.push(zone);
+    }
+
+    pub fn show_zones(&self) -> bool {
+        self.show_zones
+    }
+
+    pub fn set_show_zones(&mut self, show: bool) {
+        self.show_zones = show;
+    }
+
+    pub fn ball_collisions(&self) -> bool {
+        self.ball_collisions
+    }
+
+    pub fn set_ball_collisions(&mut self, enabled: bool) {
+        self.ball_collisions = enabled;
+    }
+
+    pub fn ball_count(&self) -> usize {
+        self.balls.len()
+    }
+
+    // Balls are kept in spawn order, so the oldest ones are at the front
+    pub fn cull_oldest_balls(&mut self, fraction: f32) {
+        let amount = (self.balls.len() as f32 * fraction).ceil() as usize;
+        self.balls.drain(..amount.min(self.balls.len()));
+        if self.shot_in_progress && self.balls.is_empty() {
+            self.finish_shot();
+        }
+    }
+
+    // For whatever is embedding the simulation to get at single balls. Nothing records
+    // changes made through these, since there's no replay recording to feed them into yet.
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "API for embedding the simulation, the game doesn't need it"
+        )
+    )]
+    pub fn ball(&self, handle: BallHandle) -> Option<&Ball> {
+        self.balls.iter().find(|ball| ball.handle == Some(handle))
+    }
+
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "API for embedding the simulation, the game doesn't need it"
+        )
+    )]
+    fn ball_mut(&mut self, handle: BallHandle) -> Option<&mut Ball> {
+        self.balls
+            .iter_mut()
+            .find(|ball| ball.handle == Some(handle))
+    }
+
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "API for embedding the simulation, the game doesn't need it"
+        )
+    )]
+    pub fn remove_ball(&mut self, handle: BallHandle) -> bool {
+        let Some(index) = self
+            .balls
+            .iter()
+            .position(|ball| ball.handle == Some(handle))
+        else {
+            return false;
+        };
+        self.balls.remove(index);
+
+        // Removing the last ball ends the shot just like draining it would
+        if self.shot_in_progress && self.balls.is_empty() {
+            self.finish_shot();
+        }
+        true
+    }
+
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "API for embedding the simulation, the game doesn't need it"
+        )
+    )]
+    pub fn set_ball_velocity(&mut self, handle: BallHandle, velocity: Point<f32>) -> bool {
+        let gravity = self.physics.gravity;
+        match self.ball_mut(handle) {
+            Some(ball) => {
+                ball.place(ball.pos, velocity, gravity);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "API for embedding the simulation, the game doesn't need it"
+        )
+    )]
+    pub fn apply_impulse(&mut self, handle: BallHandle, dv: Point<f32>) -> bool {
+        let gravity = self.physics.gravity;
+        match self.ball_mut(handle) {
+            Some(ball) => {
+                ball.place(ball.pos, ball.velocity + dv, gravity);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // A shot is decided once every ball is below everything it could still collide with
+    // and falling, and can't make it into the bucket, so nothing can change before it
+    // drains.
+    pub fn shot_is_decided(&self) -> bool {
+        let lowest_surface = self
+            .pegs
+            .iter()
+            .map(Peg::lowest_bottom)
+            .chain(self.pegs.iter().filter_map(Peg::lowest_pull))
+            .chain(self.zones.iter().map(|zone| zone.region.bottom()))
+            .chain(
+                self.flippers
+                    .iter()
+                    .filter(|_| self.flippers_active())
+                    .map(Flipper::lowest_reach),
+            )
+            .fold(f32::NEG_INFINITY, f32::max);
+        let clock = self.clock.as_secs_f32();
+        let catchable = |ball: &Ball| {
+            self.bucket
+                .as_ref()
+                .is_some_and(|bucket| bucket.could_catch(ball, &self.physics, clock))
+        };
+
+        !self.balls.is_empty()
+            && self.balls.iter().all(|ball| {
+                ball.pos.y - ball.radius > lowest_surface
+                    && ball.velocity.y > 0.0
+                    && !catchable(ball)
+            })
+    }
+
+    // What deciding whether a peg is still reachable needs from the rest of the board,
+    // which is the same for every peg
+    fn reach(&self) -> Reach {
+        // A ball going round through a zone or a portal can pick up energy without limit,
+        // and so can one getting batted about by flippers or moving pegs
+        let unbounded = !self.zones.is_empty()
+            || !self.portals.is_empty()
+            || self.flippers_active()
+            || self
+                .pegs
+                .iter()
+                .any(|peg| peg.motion.is_some() || peg.angular_velocity != 0.0);
+        let reference = self.energy_reference();
+        let gravity = self.physics.gravity;
+        // Magnets can hand out extra energy on the way
+        let boost: f32 = self
+            .pegs
+            .iter()
+            .map(|peg| peg.pull_depth(self.physics.ball_radius))
+            .sum();
+        // A bumper can send a ball off with more than it had, but no more than leaving the
+        // top of the bumper as fast as bumpers go
+        let bumped = self
+            .pegs
+            .iter()
+            .filter(|peg| matches!(peg.peg_type, PegType::Bumper { .. }))
+            .map(|peg| {
+                let center = peg.motion.map_or(peg.body.pos.y, |m| m.highest());
+                let top = center - peg.body.bounding_radius() - self.physics.ball_radius;
+                energy::kinetic(Point::new(0.0, Peg::BUMPER_MAX_SPEED))
+                    + energy::potential(Point::new(0.0, top), gravity, reference)
+            })
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        let (highest_ball, energy) = self.balls.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(highest, energy), ball| {
+                (
+                    highest.min(ball.pos.y),
+                    energy.max(ball.total_energy(reference, gravity).max(bumped)),
+                )
+            },
+        );
+        Reach {
+            unbounded,
+            highest_ball,
+            energy: energy + boost,
+        }
+    }
+
+    // Conservative check for whether any ball could still touch the peg. A ball can't
+    // climb above the height where all of its energy is potential energy, so pegs
+    // higher than that are out of reach for good.
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "rendering checks every peg at once through reaches_peg"
+        )
+    )]
+    pub fn peg_still_reachable(&self, peg_idx: usize) -> bool {
+        self.reaches_peg(&self.reach(), peg_idx)
+    }
+
+    fn reaches_peg(&self, reach: &Reach, peg_idx: usize) -> bool {
+        if reach.unbounded {
+            return true;
+        }
+        let peg = &self.pegs[peg_idx];
+        let contact = Point::new(
+            peg.body.pos.x,
+            peg.lowest_bottom() + self.physics.ball_radius,
+        );
+        let required = energy::potential(contact, self.physics.gravity, self.energy_reference());
+        reach.highest_ball <= contact.y || reach.energy >= required
+    }
+
+    fn finish_shot(&mut self) {
+        self.shot_in_progress = false;
+        for peg in &mut self.pegs {
+            peg.is_hit = false;
+        }
+        self.powerups.tick_shot_boundary();
+        if self.powerups.consume(PowerUp::MirrorWorld) {
+            self.mirror_board();
+        }
+    }
+
+    // Everything on the board swaps sides. The flippers are the same either way round.
+    fn mirror_board(&mut self) {
+        let axis = sdl::WINDOW_WIDTH as f32 / 2.0;
+        let from = self.pegs.iter().map(|peg| peg.body.pos).collect();
+        for peg in &mut self.pegs {
+            peg.mirror(axis);
+        }
+        if let Some(bucket) = &mut self.bucket {
+            bucket.path = bucket.path.mirrored(axis);
+            bucket.pos.x = 2.0 * axis - bucket.pos.x;
+        }
+        self.rebuild_peg_grid();
+        self.mirroring = Some(Mirroring {
+            from,
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    // No new shots until the board is done flipping
+    pub fn is_mirroring(&self) -> bool {
+        self.mirroring.is_some()
+    }
+
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "API for embedding the simulation, the game doesn't need it"
+        )
+    )]
+    pub fn powerups(&self) -> &ActivePowerups {
+        &self.powerups
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn caught(&self) -> u32 {
+        self.caught
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        if let Some(mirroring) = &mut self.mirroring {
+            mirroring.elapsed += delta;
+            if mirroring.elapsed >= Self::MIRROR_DURATION {
+                self.mirroring = None;
+            }
+        }
+
+        // Split the tick up so that no ball moves more than half its radius in one go
+        let d = delta.as_secs_f32();
+        let fastest = self
+            .balls
+            .iter()
+            .filter(|ball| ball.state == BallState::Free)
+            .map(|ball| (ball.velocity.length() + self.physics.gravity.length() * d) * d)
+            .fold(0.0, f32::max)
+            + self.fastest_obstacle() * d;
+        let substeps = ((fastest / (self.physics.ball_radius / 2.0)).ceil() as u32)
+            .clamp(1, Self::MAX_SUBSTEPS);
+        for _ in 0..substeps {
+            self.step(delta / substeps);
+        }
+        self.remove_broken_pegs();
+
+        if self.shot_in_progress && self.balls.is_empty() {
+            self.finish_shot();
+        }
+
+        self.tick += 1;
+    }
+
+    fn step(&mut self, delta: Duration) {
+        debug_assert_eq!(self.peg_grid.len(), self.pegs.len(), "peg grid is stale");
+        let reference = self.energy_reference();
+        let physics = self.physics;
+        let clock = self.clock.as_secs_f32();
+        // Moving pegs can come from outside the ball's own path
+        let peg_reach = self.fastest_peg() * delta.as_secs_f32();
+        let bucket_walls = self.bucket.as_ref().map(Bucket::walls);
+        let bucket_velocity = self
+            .bucket
+            .as_ref()
+            .map_or(Point::zero(), |b| b.path.velocity_at(clock));
+        let pegs_solid = self.mirroring.is_none();
+        let magnets: Vec<usize> = (0..self.pegs.len())
+            .filter(|&i| matches!(self.pegs[i].peg_type, PegType::Magnet { .. }))
+            .collect();
+        let flippers_active = self.flippers_active();
+        if flippers_active {
+            for flipper in &mut self.flippers {
+                flipper.begin_step(delta.as_secs_f32());
+            }
+        }
+        let flipper_bodies = if flippers_active {
+            self.flippers.each_ref().map(|f| Some(f.body()))
+        } else {
+            [None, None]
+        };
+        self.balls.retain_mut(|ball| {
+            if ball.pos.y > self.kill_plane {
+                return false;
+            }
+
+            if let BallState::Captured {
+                by_peg,
+                release_tick,
+            } = ball.state
+            {
+                if self.tick < release_tick {
+                    return true;
+                }
+
+                let cannon = &self.pegs[by_peg];
+                if let PegType::Cannon { direction, speed } = cannon.peg_type {
+                    let dir = Point::from(PolarPoint::new(direction, 1.0));
+                    let rim = cannon.body.bounding_radius() + ball.radius;
+                    ball.place(cannon.body.pos + dir * rim, dir * speed, physics.gravity);
+                }
+                ball.state = BallState::Free;
+            }
+
+            // Moving with the average velocity over the tick is exact under constant
+            // gravity, so free fall conserves energy. Magnets and zones are held constant
+            // over the tick as well.
+            let pull = magnets.iter().fold(Point::zero(), |acc, &i| {
+                acc + self.pegs[i].pull(ball.pos, ball.radius)
+            }) + self
+                .zones
+                .iter()
+                .filter(|zone| zone.region.contains(ball.pos))
+                .fold(Point::zero(), |acc, zone| acc + zone.acceleration);
+            let dv = (physics.gravity + pull) * delta.as_secs_f32();
+            ball.apply_spin(delta.as_secs_f32());
+
+            // Lifting a wedged ball can move it up to a few radii
+            let (min, max) = ball.swept_bounds(Duration::ZERO, 4.0 * ball.radius);
+            let nearby = self.peg_grid.query(min, max);
+            if pegs_solid {
+                // Pegs broken earlier in the update are still there until it's over
+                let solid = nearby
+                    .iter()
+                    .map(|&i| &self.pegs[i])
+                    .filter(|peg| peg.hits_remaining > 0);
+                ball.depenetrate(solid, physics.gravity);
+            }
+
+            // Bounce off whichever peg is hit first, then keep going with what's left of
+            // the tick, so a ball can't slip between pegs that are close together
+            let mut start = ball.pos;
+            let mut left = 1.0;
+            let mut resting_on = None;
+            // Over the rest of the step. Gravity can't pull a resting ball any further into
+            // what it's resting on.
+            let average_velocity =
+                |velocity: Point<f32>, left: f32, resting_on: Option<(Point<f32>, Point<f32>)>| {
+                    let average = velocity + dv * (left / 2.0);
+                    match resting_on {
+                        Some((normal, surface_velocity)) => {
+                            average - normal * normal.dot(average - surface_velocity).min(0.0)
+                        }
+                        None => average,
+                    }
+                };
+            for _ in 0..Self::MAX_COLLISIONS_PER_TICK {
+                let remaining = delta.mul_f32(left);
+                let velocity = ball.velocity;
+                ball.velocity = average_velocity(velocity, left, resting_on);
+
+                let (min, max) = ball.swept_bounds(remaining, peg_reach);
+                let Some((obstacle, collision)) = self
+                    .peg_grid
+                    .query(min, max)
+                    .into_iter()
+                    .filter(|&i| pegs_solid && self.pegs[i].hits_remaining > 0)
+                    .filter_map(|i| {
+                        let peg = &self.pegs[i];
+                        let near = peg.body.closest_surface_point(ball.pos);
+                        let collision = ball.will_collide(
+                            &peg.body,
+                            peg.surface_velocity(near, clock),
+                            remaining,
+                        )?;
+                        Some((Obstacle::Peg(i), collision))
+                    })
+                    .chain(
+                        ball.will_hit_wall(remaining, physics.walls)
+                            .map(|c| (Obstacle::Wall, c)),
+                    )
+                    .chain(bucket_walls.iter().flatten().filter_map(|wall| {
+                        let collision = ball.will_collide(wall, bucket_velocity, remaining)?;
+                        Some((Obstacle::Bucket, collision))
+                    }))
+                    .chain(
+                        self.flippers
+                            .iter()
+                            .zip(&flipper_bodies)
+                            .enumerate()
+                            .filter_map(|(i, (flipper, body))| {
+                                let body = body.as_ref()?;
+                                let near = body.closest_surface_point(ball.pos);
+                                let collision = ball.will_collide(
+                                    body,
+                                    flipper.surface_velocity(near),
+                                    remaining,
+                                )?;
+                                Some((Obstacle::Flipper(i), collision))
+                            }),
+                    )
+                    .min_by(|(_, c1), (_, c2)| c1.toi.total_cmp(&c2.toi))
+                else {
+                    ball.velocity = velocity;
+                    break;
+                };
+                // Bounces use the velocity at the moment of impact. Bouncing the average
+                // one would turn the gravity from after the impact into extra rebound.
+                ball.velocity = velocity + dv * (left * collision.toi);
+
+                // Walls aren't pegs but bounce like the default one
+                let (mut restitution, mut friction) = (physics.elasticity, 0.0);
+                let mut boost = 0.0;
+                let mut surface_velocity = Point::zero();
+                let mut struck = None;
+                let contact = collision.point - collision.normal * ball.radius;
+                match obstacle {
+                    Obstacle::Wall => {}
+                    Obstacle::Bucket => surface_velocity = bucket_velocity,
+                    Obstacle::Flipper(i) => {
+                        surface_velocity = self.flippers[i].surface_velocity(contact);
+                    }
+                    Obstacle::Peg(i) => {
+                        let exit = self.portals.iter().find_map(|&(a, b)| match i {
+                            _ if i == a => Some(b),
+                            _ if i == b => Some(a),
+                            _ => None,
+                        });
+                        let peg = &mut self.pegs[i];
+                        if let (Some(exit), PegType::Portal { channel }) = (exit, peg.peg_type)
+                            && ball.portal_ready(channel, self.tick)
+                        {
+                            peg.is_hit = true;
+                            // Out just clear of the other portal, so it isn't hit straight away
+                            let exit = &self.pegs[exit].body;
+                            let out = exit.pos
+                                + ball.velocity.normalized()
+                                    * (exit.bounding_radius() + ball.radius + 1.0);
+                            ball.add_energy_budget(pull.dot(collision.point - start));
+                            ball.pos = collision.point;
+                            ball.place(out, ball.velocity, physics.gravity);
+                            ball.portal_cooldowns
+                                .push((channel, self.tick + Self::PORTAL_COOLDOWN_TICKS));
+                            start = out;
+                            left *= 1.0 - collision.toi;
+                            continue;
+                        }
+                        if matches!(peg.peg_type, PegType::Cannon { .. }) && !peg.is_hit {
+                            peg.is_hit = true;
+                            ball.add_energy_budget(pull.dot(collision.point - start));
+                            ball.place(peg.body.pos, Point::zero(), physics.gravity);
+                            ball.state = BallState::Captured {
+                                by_peg: i,
+                                release_tick: self.tick + Self::CANNON_HOLD_TICKS,
+                            };
+                            return true;
+                        }
+                        if let PegType::PowerUp(kind) = peg.peg_type
+                            && !peg.is_hit
+                        {
+                            self.powerups.grant(kind);
+                        }
+                        peg.is_hit = true;
+                        if let PegType::Bumper { boost: kick } = peg.peg_type {
+                            boost = kick;
+                            peg.last_hit_tick = Some(self.tick);
+                        }
+                        (restitution, friction) = (peg.restitution, peg.friction);
+                        surface_velocity = peg.surface_velocity(contact, clock);
+                        struck = Some(i);
+                    }
+                }
+
+                // The bounce happens relative to the surface, so a moving peg carries the
+                // ball along with it. Whatever that adds or takes away is legitimate.
+                let before = energy::kinetic(ball.velocity);
+                ball.velocity -= surface_velocity;
+
+                // Too slow to bounce, so the ball stays on the surface instead of
+                // hopping up and down on it forever
+                if -collision.normal.dot(ball.velocity) < Ball::RESTING_SPEED {
+                    restitution = 0.0;
+                    resting_on = Some((collision.normal, surface_velocity));
+                } else if let Some(i) = struck {
+                    // It still gets bounced off on the hit that breaks it
+                    let hits = &mut self.pegs[i].hits_remaining;
+                    *hits = hits.saturating_sub(1);
+                }
+                ball.bounce(collision.normal, restitution, friction);
+                ball.velocity += surface_velocity;
+                if surface_velocity != Point::zero() {
+                    ball.add_energy_budget(energy::kinetic(ball.velocity) - before);
+                }
+                if boost > 0.0 {
+                    let before = energy::kinetic(ball.velocity);
+                    let cap = ball.velocity.length().max(Peg::BUMPER_MAX_SPEED);
+                    let kicked = ball.velocity + collision.normal * boost;
+                    ball.velocity = kicked.with_length(kicked.length().min(cap));
+                    ball.add_energy_budget(energy::kinetic(ball.velocity) - before);
+                }
+                ball.pos = collision.point;
+                left *= 1.0 - collision.toi;
+            }
+            ball.pos +=
+                average_velocity(ball.velocity, left, resting_on) * (delta.as_secs_f32() * left);
+            // Only matters if the collision limit was hit with time left over
+            if physics.walls == Walls::Solid {
+                let right = sdl::WINDOW_WIDTH as f32 - ball.radius;
+                ball.pos.x = ball.pos.x.clamp(ball.radius, right);
+            }
+            ball.velocity += dv * left;
+            // Gravity shows up as potential energy, but magnets and zones don't
+            ball.add_energy_budget(pull.dot(ball.pos - start));
+
+            // The surface holds the ball up against the rest of the step's gravity, which
+            // leaves only the part along the surface to roll it off
+            if let Some((normal, surface_velocity)) = resting_on {
+                ball.velocity -= normal * normal.dot(ball.velocity - surface_velocity).min(0.0);
+            }
+
+            // for peg in &self.pegs {
+            //     if peg.body.extend(Ball::RADIUS).contains(ball.pos) {
+            //         println!("Ball is inside peg at {}", peg.body.pos);
+            //     }
+            // }
+
+            ball.gained_energy = ball.has_gained_energy(reference, physics.gravity);
+
+            if self
+                .bucket
+                .as_ref()
+                .is_some_and(|b| b.catches(start, ball.pos))
+            {
+                self.caught += 1;
+                return false;
+            }
+            true
+        });
+
+        if self.ball_collisions {
+            self.collide_balls();
+        }
+        if flippers_active {
+            for flipper in &mut self.flippers {
+                flipper.end_step(delta.as_secs_f32());
+            }
+        }
+        self.clock += delta;
+        self.move_pegs();
+        self.spin_pegs(delta);
+    }
+
+    // Pushes overlapping balls apart and bounces them off each other. A ball in a clump
+    // can be pushed into another one, so this goes over all of them a few times.
+    fn collide_balls(&mut self) {
+        for _ in 0..Self::BALL_RELAXATION_PASSES {
+            for i in 1..self.balls.len() {
+                let (before, after) = self.balls.split_at_mut(i);
+                let b = &mut after[0];
+                if b.state != BallState::Free {
+                    continue;
+                }
+                for a in before.iter_mut().filter(|a| a.state == BallState::Free) {
+                    a.collide_with(b, self.physics.elasticity, self.physics.gravity);
+                }
+            }
+        }
+    }
+}
+
+impl Render for Poggle {
+    fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
+    where
+        T: sdl2::render::RenderTarget,
+    {
+        for ball in &self.balls {
+            if ball.state == BallState::Free {
+                ball.render_colored(canvas, ball.color(self.ball_coloring))?;
+            }
+        }
+
+        let reach = self.reach();
+        for (i, peg) in self.pegs.iter().enumerate() {
+            let sliding;
+            let peg = match &self.mirroring {
+                Some(mirroring) => {
+                    let t = mirroring.elapsed.as_secs_f32() / Self::MIRROR_DURATION.as_secs_f32();
+                    let from = mirroring.from[i];
+                    let mut moved = peg.clone();
+                    moved.body.pos = from + from.to(peg.body.pos) * t;
+                    sliding = moved;
+                    &sliding
+                }
+                None => peg,
+            };
+            if peg.is_hit && !self.reaches_peg(&reach, i) {
+                peg.render_hollow(canvas)?;
+            } else {
+                peg.render(canvas)?;
+            }
+            if let PegType::Portal { channel } = peg.peg_type {
+                let swirl = self.tick as f32 * 0.15;
+                let radius = peg.body.bounding_radius();
+                canvas.set_draw_color(Peg::PORTAL_COLORS[channel as usize % 4][1]);
+                for arm in 0..3 {
+                    let angle = swirl + arm as f32 * consts::TAU / 3.0;
+                    let tip = Point::from(PolarPoint::new(angle, radius * 0.8));
+                    let bend = Point::from(PolarPoint::new(angle - 1.0, radius * 0.4));
+                    let center = peg.body.pos;
+                    canvas.draw_line(center, center + bend)?;
+                    canvas.draw_line(center + bend, center + tip)?;
+                }
+            }
+            if peg
+                .last_hit_tick
+                .is_some_and(|tick| self.tick - tick < Peg::FLASH_TICKS)
+            {
+                canvas.set_draw_color(peg.color());
+                draw_circle(
+                    canvas,
+                    peg.body.pos.x as u32,
+                    peg.body.pos.y as u32,
+                    peg.body.bounding_radius() as u32 + 3,
+                )?;
+            }
+        }
+
+        // Balls held by a cannon are drawn on top of it
+        for ball in &self.balls {
+            if ball.state != BallState::Free {
+                ball.render_colored(canvas, ball.color(self.ball_coloring))?;
+            }
+        }
+
+        if let Some(bucket) = &self.bucket {
+            bucket.render(canvas)?;
+        }
+        if self.show_zones {
+            for zone in &self.zones {
+                zone.render(canvas)?;
+            }
+        }
+        if self.flippers_active() {
+            for flipper in &self.flippers {
+                flipper.render(canvas)?;
+            }
+        }
+        self.powerups.render(canvas)?;
+
+        // canvas.set_draw_color(Color::GREEN);
         // if let Some(ball) = &self.ball {
         //     for peg in &self.pegs {
         //         if let Some(collision) = ball.will_collide(
@@ -336,37 +1974,51 @@ impl Render for Poggle {
     }
 }
 
-impl Render for Ball {
-    fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
+impl Ball {
+    fn color(&self, coloring: BallColoring) -> Color {
+        match coloring {
+            BallColoring::Flat => Ball::COLOR,
+            BallColoring::Heat => palette::heat(self.velocity.length(), Ball::HEAT_MAX_SPEED),
+            BallColoring::Energy => {
+                if self.gained_energy {
+                    Color::GREEN
+                } else {
+                    Color::RED
+                }
+            }
+        }
+    }
+
+    fn render_colored<T>(
+        &self,
+        canvas: &mut sdl2::render::Canvas<T>,
+        color: Color,
+    ) -> Result<(), String>
     where
         T: sdl2::render::RenderTarget,
     {
-        let start = Ball::new(self.start, Point::zero());
-        if self.total_energy() > start.total_energy() {
-            // println!(
-            //     "Ball starting at {} has an energy of {:.2} (started at {:.2})",
-            //     self.start,
-            //     self.total_energy(),
-            //     start.total_energy()
-            // );
-            canvas.set_draw_color(Color::GREEN);
-        } else {
-            canvas.set_draw_color(Color::RED);
-        }
-
+        canvas.set_draw_color(color);
         draw_circle_filled(
             canvas,
             self.pos.x as u32,
             self.pos.y as u32,
-            Ball::RADIUS as u32,
+            self.radius as u32,
         )?;
         canvas.set_draw_color(Color::BLACK);
         draw_circle(
             canvas,
             self.pos.x as u32,
             self.pos.y as u32,
-            Ball::RADIUS as u32,
+            self.radius as u32,
         )?;
+        // Marker that turns with the ball so spin is visible
+        let marker = self.pos + Point::new(self.radius * 0.5, 0.0).rotated(self.rotation);
+        canvas.fill_rect(sdl2::rect::Rect::new(
+            marker.x as i32 - 1,
+            marker.y as i32 - 1,
+            2,
+            2,
+        ))?;
         canvas.set_draw_color(Color::MAGENTA);
         canvas.draw_line(self.pos, self.pos + self.velocity * 0.10)?;
         canvas.set_draw_color(Color::GREEN);
@@ -381,12 +2033,58 @@ impl Render for Ball {
     }
 }
 
-impl Render for Peg {
+impl Render for Ball {
     fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
     where
         T: sdl2::render::RenderTarget,
     {
-        let color = match self.peg_type {
+        self.render_colored(canvas, Ball::COLOR)
+    }
+}
+
+impl Peg {
+    // Bumpers can't speed a ball up past this, so chains of them can't run away
+    const BUMPER_MAX_SPEED: f32 = 1500.0;
+    const FLASH_TICKS: u64 = 10;
+    // Body and swirl of the portals on each channel
+    const PORTAL_COLORS: [[Color; 2]; 4] = [
+        [Color::RGB(0, 120, 255), Color::RGB(150, 220, 255)],
+        [Color::RGB(255, 120, 0), Color::RGB(255, 220, 150)],
+        [Color::RGB(0, 170, 80), Color::RGB(170, 255, 190)],
+        [Color::RGB(200, 0, 120), Color::RGB(255, 170, 220)],
+    ];
+
+    // How fast the part of the peg at `point` is moving, from riding along its path and
+    // from spinning around its center
+    fn surface_velocity(&self, point: Point<f32>, time: f32) -> Point<f32> {
+        let linear = self.motion.map_or(Point::zero(), |m| m.velocity_at(time));
+        let arm = self.body.pos.to(point);
+        linear + Point::new(-arm.y, arm.x) * self.angular_velocity
+    }
+
+    // The lowest the bottom of the peg ever gets, wherever it is on its path and however
+    // it's turned
+    fn lowest_bottom(&self) -> f32 {
+        let bottom = if self.angular_velocity == 0.0 {
+            self.body.bottom()
+        } else {
+            self.body.pos.y + self.body.bounding_radius()
+        };
+        match &self.motion {
+            Some(motion) => bottom - self.body.pos.y + motion.lowest(),
+            None => bottom,
+        }
+    }
+
+    // Breakable pegs are darker the more hits they have left
+    fn color(&self) -> Color {
+        let color = self.base_color();
+        let extra = self.hits_remaining.saturating_sub(1).min(3);
+        palette::lerp(color, Color::BLACK, extra as f32 * 0.2)
+    }
+
+    fn base_color(&self) -> Color {
+        match self.peg_type {
             PegType::Standard => {
                 if self.is_hit {
                     Color::YELLOW
@@ -397,11 +2095,114 @@ impl Render for Peg {
             PegType::Target => Color::RED,
             PegType::PointBoost => Color::MAGENTA,
             PegType::PowerUp(_) => Color::GREEN,
+            PegType::Cannon { .. } => {
+                if self.is_hit {
+                    Color::RGB(60, 60, 60)
+                } else {
+                    Color::RGB(120, 120, 120)
+                }
+            }
+            PegType::Portal { channel } => Peg::PORTAL_COLORS[channel as usize % 4][0],
+            PegType::Bumper { .. } => {
+                if self.is_hit {
+                    Color::RGB(255, 200, 60)
+                } else {
+                    Color::RGB(230, 90, 0)
+                }
+            }
+            PegType::Magnet { .. } => {
+                if self.is_hit {
+                    Color::RGB(220, 130, 255)
+                } else {
+                    Color::RGB(150, 0, 200)
+                }
+            }
+        }
+    }
+
+    // Acceleration the peg gives a ball at `pos` if it's a magnet. The ball can't get any
+    // closer than touching the peg, and inside that the pull stops growing so it stays
+    // finite even right at the center.
+    fn pull(&self, pos: Point<f32>, ball_radius: f32) -> Point<f32> {
+        let PegType::Magnet { strength, range } = self.peg_type else {
+            return Point::zero();
         };
-        canvas.set_draw_color(color);
+        let offset = pos.to(self.body.pos);
+        let distance = offset.length();
+        if distance > range || distance <= f32::EPSILON {
+            return Point::zero();
+        }
+        let nearest = self.body.bounding_radius() + ball_radius;
+        offset / distance * (strength / distance.max(nearest).powi(2))
+    }
+
+    // The most energy the pull can ever give a ball: falling in from the edge of the range
+    // to touching the peg, and then the rest of the way to the center at the pull there
+    fn pull_depth(&self, ball_radius: f32) -> f32 {
+        let PegType::Magnet { strength, .. } = self.peg_type else {
+            return 0.0;
+        };
+        2.0 * strength / (self.body.bounding_radius() + ball_radius)
+    }
+
+    fn mirror(&mut self, axis: f32) {
+        self.body.mirror(axis);
+        self.motion = self.motion.map(|m| m.mirrored(axis));
+        self.angular_velocity = -self.angular_velocity;
+        if let PegType::Cannon { direction, .. } = &mut self.peg_type {
+            *direction = consts::PI - *direction;
+        }
+    }
+
+    // The lowest a magnet can reach out and pull a ball back up from
+    fn lowest_pull(&self) -> Option<f32> {
+        let PegType::Magnet { range, .. } = self.peg_type else {
+            return None;
+        };
+        let center = self.motion.map_or(self.body.pos.y, |m| m.lowest());
+        Some(center + range)
+    }
+
+    // Hit pegs that no ball can reach anymore are drawn as outlines only
+    fn render_hollow<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
+    where
+        T: sdl2::render::RenderTarget,
+    {
+        canvas.set_draw_color(self.color());
         match &self.body.shape {
             Shape::Circle { radius } => {
-                draw_circle_filled(
+                draw_circle(
+                    canvas,
+                    self.body.pos.x as u32,
+                    self.body.pos.y as u32,
+                    *radius as u32,
+                )?;
+            }
+            Shape::Polygon {
+                points, rotation, ..
+            } => {
+                draw_polygon(canvas, points, self.body.pos, *rotation)?;
+            }
+            Shape::Rect { half_extents, .. } => {
+                canvas.draw_rect(sdl::centered_rect(self.body.pos, *half_extents))?;
+            }
+            Shape::Capsule { a, b, radius } => {
+                draw_capsule(canvas, self.body.pos + *a, self.body.pos + *b, *radius)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Render for Peg {
+    fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
+    where
+        T: sdl2::render::RenderTarget,
+    {
+        canvas.set_draw_color(self.color());
+        match &self.body.shape {
+            Shape::Circle { radius } => {
+                draw_circle_filled(
                     canvas,
                     self.body.pos.x as u32,
                     self.body.pos.y as u32,
@@ -422,8 +2223,1729 @@ impl Render for Peg {
                 //     *radius as u32 + Ball::RADIUS as u32,
                 // )?;
             }
-            Shape::Polygon { points, rotation } => todo!(),
+            Shape::Polygon {
+                points, rotation, ..
+            } => {
+                draw_polygon_filled(canvas, points, self.body.pos, *rotation)?;
+                canvas.set_draw_color(Color::BLACK);
+                draw_polygon(canvas, points, self.body.pos, *rotation)?;
+            }
+            Shape::Rect { half_extents, .. } => {
+                let rect = sdl::centered_rect(self.body.pos, *half_extents);
+                canvas.fill_rect(rect)?;
+                canvas.set_draw_color(Color::BLACK);
+                canvas.draw_rect(rect)?;
+            }
+            Shape::Capsule { a, b, radius } => {
+                let (a, b) = (self.body.pos + *a, self.body.pos + *b);
+                draw_capsule_filled(canvas, a, b, *radius)?;
+                canvas.set_draw_color(Color::BLACK);
+                draw_capsule(canvas, a, b, *radius)?;
+            }
+        }
+
+        if let PegType::Magnet { range, .. } = self.peg_type {
+            canvas.set_draw_color(Color::RGB(70, 40, 90));
+            draw_circle(
+                canvas,
+                self.body.pos.x as u32,
+                self.body.pos.y as u32,
+                range as u32,
+            )?;
+        }
+        if let PegType::Cannon { direction, .. } = self.peg_type {
+            // The barrel points where the cannon fires
+            let dir = Point::from(PolarPoint::new(direction, 1.0));
+            let side = Point::new(-dir.y, dir.x);
+            let length = self.body.bounding_radius() * 1.6;
+            canvas.set_draw_color(Color::BLACK);
+            for offset in [-1.0, 0.0, 1.0] {
+                let start = self.body.pos + side * offset;
+                canvas.draw_line(start, start + dir * length)?;
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{f32::consts, time::Duration};
+
+    use crate::{
+        energy,
+        poggle::{
+            Ball, BallColoring, BallHandle, BallState, Bucket, Collision, Flipper, ForceZone,
+            Motion, Peg, PegType, PhysicsConfig, Poggle, PowerUp, Walls,
+        },
+        powerup::{ActivePowerups, Allowance},
+        sdl,
+        shape::{Body, Point, PolarPoint, Region, Shape},
+        spatial::SpatialHash,
+    };
+
+    fn empty() -> Poggle {
+        Poggle {
+            balls: Vec::new(),
+            pegs: Vec::new(),
+            peg_grid: SpatialHash::new(std::iter::empty()),
+            tick: 0,
+            next_handle: 0,
+            kill_plane: 806.0,
+            ball_coloring: BallColoring::Flat,
+            powerups: ActivePowerups::new(),
+            ball_collisions: false,
+            physics: PhysicsConfig::default(),
+            clock: Duration::ZERO,
+            bucket: None,
+            caught: 0,
+            flippers: Poggle::flippers(),
+            shot_in_progress: false,
+            mirroring: None,
+            zones: Vec::new(),
+            portals: Vec::new(),
+            show_zones: false,
+        }
+    }
+
+    #[test]
+    fn test_stale_handle() {
+        let mut poggle = empty();
+        let a = poggle.shoot(Point::new(100.0, 100.0), Point::zero());
+        let b = poggle.shoot(Point::new(200.0, 100.0), Point::zero());
+
+        assert!(poggle.remove_ball(a));
+        assert!(!poggle.remove_ball(a));
+        assert!(poggle.ball(a).is_none());
+        assert!(!poggle.set_ball_velocity(a, Point::new(1.0, 0.0)));
+        assert!(!poggle.apply_impulse(a, Point::new(1.0, 0.0)));
+
+        // A ball spawned after a despawn must not be reachable through the old handle
+        let c = poggle.shoot(Point::new(300.0, 100.0), Point::zero());
+        assert_ne!(a, c);
+        assert!(poggle.ball(a).is_none());
+
+        assert!(poggle.apply_impulse(b, Point::new(1.0, 2.0)));
+        assert!(poggle.apply_impulse(b, Point::new(1.0, 2.0)));
+        assert_eq!(poggle.ball(b).unwrap().velocity(), Point::new(2.0, 4.0));
+        assert!(poggle.set_ball_velocity(c, Point::new(5.0, 0.0)));
+        assert_eq!(poggle.ball(c).unwrap().velocity(), Point::new(5.0, 0.0));
+    }
+
+    fn square(pos: Point<f32>, half: f32) -> Body {
+        Body {
+            pos,
+            shape: Shape::Polygon {
+                points: vec![
+                    Point::new(-half, -half),
+                    Point::new(half, -half),
+                    Point::new(half, half),
+                    Point::new(-half, half),
+                ],
+                rotation: 0.0,
+                rounding: 0.0,
+            },
+        }
+    }
+
+    fn peg(pos: Point<f32>) -> Peg {
+        Peg {
+            body: Body {
+                pos,
+                shape: Shape::Circle { radius: 6.0 },
+            },
+            is_hit: false,
+            peg_type: PegType::Standard,
+            restitution: PhysicsConfig::default().elasticity,
+            friction: 0.0,
+            motion: None,
+            angular_velocity: 0.0,
+            last_hit_tick: None,
+            // Enough to outlast any test that isn't about breaking pegs
+            hits_remaining: u8::MAX,
+        }
+    }
+
+    fn sturdy(pegs: Vec<Peg>) -> Vec<Peg> {
+        pegs.into_iter()
+            .map(|peg| Peg {
+                hits_remaining: u8::MAX,
+                ..peg
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_remove_last_ball_finishes_shot() {
+        let mut poggle = empty();
+        poggle.add_peg(peg(Point::new(100.0, 100.0)));
+        poggle.pegs[0].is_hit = true;
+        let a = poggle.shoot(Point::new(100.0, 300.0), Point::zero());
+        let b = poggle.shoot(Point::new(200.0, 300.0), Point::zero());
+
+        poggle.remove_ball(a);
+        assert!(poggle.pegs[0].is_hit);
+        poggle.remove_ball(b);
+        assert!(!poggle.pegs[0].is_hit);
+    }
+
+    #[test]
+    fn test_shot_is_decided() {
+        let mut poggle = empty();
+        poggle.add_peg(peg(Point::new(100.0, 300.0)));
+        poggle.add_peg(peg(Point::new(300.0, 500.0)));
+        assert!(!poggle.shot_is_decided());
+
+        // Below the upper peg but not the lower one
+        let a = poggle.shoot(Point::new(100.0, 400.0), Point::new(0.0, 100.0));
+        assert!(!poggle.shot_is_decided());
+
+        poggle.remove_ball(a);
+        let b = poggle.shoot(Point::new(100.0, 520.0), Point::new(0.0, 100.0));
+        assert!(poggle.shot_is_decided());
+
+        // Still rising after a bounce, so it could reach the pegs again
+        poggle.set_ball_velocity(b, Point::new(0.0, -100.0));
+        assert!(!poggle.shot_is_decided());
+        poggle.set_ball_velocity(b, Point::new(0.0, 100.0));
+
+        // Every ball has to be decided
+        poggle.shoot(Point::new(100.0, 200.0), Point::new(0.0, 100.0));
+        assert!(!poggle.shot_is_decided());
+    }
+
+    #[test]
+    fn test_shot_is_decided_on_default_board() {
+        let mut poggle = Poggle::default();
+        poggle.balls.clear();
+        // Below the last row of pegs, over by the wall
+        poggle.shoot(Point::new(40.0, 730.0), Point::new(0.0, 100.0));
+        assert!(poggle.shot_is_decided());
+    }
+
+    #[test]
+    fn test_shot_is_decided_by_bucket_reach() {
+        let mut poggle = empty();
+        poggle.bucket = Some(still_bucket(300.0));
+        let a = poggle.shoot(Point::new(300.0, 600.0), Point::new(0.0, 100.0));
+        assert!(!poggle.shot_is_decided());
+
+        poggle.set_ball_velocity(a, Point::new(200.0, 100.0));
+        assert!(poggle.shot_is_decided());
+        poggle.remove_ball(a);
+
+        // Drifting over from the side to land in it
+        let b = poggle.shoot(Point::new(500.0, 600.0), Point::new(-400.0, 100.0));
+        assert!(!poggle.shot_is_decided());
+        poggle.set_ball_velocity(b, Point::new(-200.0, 100.0));
+        assert!(poggle.shot_is_decided());
+
+        // Already past the opening
+        poggle.remove_ball(b);
+        poggle.shoot(Point::new(300.0, 710.0), Point::new(0.0, 100.0));
+        assert!(poggle.shot_is_decided());
+    }
+
+    #[test]
+    fn test_shot_is_decided_on_moving_bucket() {
+        let mut poggle = Poggle::default();
+        poggle.balls.clear();
+        // Halfway across, where it's going fastest
+        poggle.clock = Duration::from_millis(1500);
+        let bucket = poggle.bucket.as_mut().unwrap();
+        bucket.pos = bucket.path.position_at(1.5);
+        let now = bucket.pos.x;
+        // Right where the bucket is going to be once the ball gets down to it
+        let drop = bucket.pos.y - 730.0;
+        let t = 2.0 * drop / (100.0 + (100.0f32.powi(2) + 2.0 * 550.0 * drop).sqrt());
+        let later = bucket.path.position_at(1.5 + t).x;
+
+        let a = poggle.shoot(Point::new(later, 730.0), Point::new(0.0, 100.0));
+        assert!(!poggle.shot_is_decided());
+        poggle.remove_ball(a);
+        poggle.shoot(Point::new(now, 730.0), Point::new(0.0, 100.0));
+        assert!(poggle.shot_is_decided());
+    }
+
+    #[test]
+    fn test_flippers_keep_shot_undecided() {
+        let mut poggle = empty();
+        poggle.shoot(Point::new(100.0, 650.0), Point::new(0.0, 100.0));
+        assert!(poggle.shot_is_decided());
+
+        // The left flipper can still bat it back up
+        poggle.powerups.grant(PowerUp::Flippers);
+        assert!(!poggle.shot_is_decided());
+    }
+
+    #[test]
+    fn test_fast_ball_has_not_gained_energy() {
+        let mut poggle = empty();
+        let a = poggle.shoot(Point::new(100.0, 100.0), Point::new(1500.0, -1500.0));
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+        assert!(
+            !poggle
+                .ball(a)
+                .unwrap()
+                .has_gained_energy(reference, gravity)
+        );
+
+        for _ in 0..100 {
+            poggle.update(Duration::from_secs(1) / 165);
+            assert!(
+                !poggle
+                    .ball(a)
+                    .unwrap()
+                    .has_gained_energy(reference, gravity)
+            );
+        }
+    }
+
+    #[test]
+    fn test_energy_gain_is_flagged() {
+        let mut poggle = empty();
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+        let a = poggle.shoot(Point::new(100.0, 100.0), Point::new(200.0, 0.0));
+        let ball = poggle.ball_mut(a).unwrap();
+
+        // A rigged reflection that comes out faster than it went in
+        ball.velocity = Point::new(-300.0, 0.0);
+        assert!(ball.has_gained_energy(reference, gravity));
+
+        // Unless the extra energy was accounted for
+        ball.add_energy_budget(
+            energy::kinetic(Point::new(-300.0, 0.0)) - energy::kinetic(Point::new(200.0, 0.0)),
+        );
+        assert!(!ball.has_gained_energy(reference, gravity));
+    }
+
+    #[test]
+    fn test_peg_still_reachable() {
+        let mut poggle = empty();
+        // Touching the peg needs the ball's center at y = 612, i.e. an energy of
+        // (800 - 612) * 550 = 103400
+        poggle.add_peg(peg(Point::new(100.0, 600.0)));
+
+        // At rest at y = 700: (800 - 700) * 550 = 55000
+        let a = poggle.shoot(Point::new(100.0, 700.0), Point::zero());
+        assert!(!poggle.peg_still_reachable(0));
+
+        // Moving up at 400 px/s adds 400^2 / 2 = 80000, enough to get back up to the peg
+        poggle.set_ball_velocity(a, Point::new(0.0, -400.0));
+        assert!(poggle.peg_still_reachable(0));
+
+        // 200 px/s sideways (20000) isn't enough no matter where it bounces
+        poggle.set_ball_velocity(a, Point::new(200.0, 0.0));
+        assert!(!poggle.peg_still_reachable(0));
+
+        // Any ball above the peg can still fall onto it
+        poggle.shoot(Point::new(500.0, 100.0), Point::zero());
+        assert!(poggle.peg_still_reachable(0));
+    }
+
+    #[test]
+    fn test_peg_reachable_with_energy_sources() {
+        let mut poggle = empty();
+        poggle.add_peg(peg(Point::new(100.0, 600.0)));
+        poggle.shoot(Point::new(100.0, 700.0), Point::zero());
+        assert!(!poggle.peg_still_reachable(0));
+
+        // Flippers can bat the ball back up
+        poggle.powerups.grant(PowerUp::Flippers);
+        assert!(poggle.peg_still_reachable(0));
+        poggle.powerups = ActivePowerups::new();
+
+        // So can anything moving
+        poggle.add_peg(Peg {
+            angular_velocity: 2.0,
+            ..peg(Point::new(500.0, 600.0))
+        });
+        assert!(poggle.peg_still_reachable(0));
+    }
+
+    #[test]
+    fn test_cull_oldest_balls() {
+        let mut poggle = empty();
+        let handles: Vec<_> = (0..8)
+            .map(|i| poggle.shoot(Point::new(i as f32 * 10.0, 100.0), Point::zero()))
+            .collect();
+
+        poggle.cull_oldest_balls(0.25);
+        assert_eq!(poggle.ball_count(), 6);
+        assert!(poggle.ball(handles[1]).is_none());
+        assert!(poggle.ball(handles[2]).is_some());
+    }
+
+    #[test]
+    fn test_cannon_capture_and_release() {
+        let mut poggle = empty();
+        let mut cannon = peg(Point::new(100.0, 300.0));
+        cannon.peg_type = PegType::Cannon {
+            direction: -std::f32::consts::FRAC_PI_2,
+            speed: 500.0,
+        };
+        poggle.add_peg(cannon);
+        let a = poggle.shoot(Point::new(100.0, 250.0), Point::zero());
+
+        let delta = Duration::from_secs(1) / 165;
+        let mut ticks = 0;
+        while poggle.ball(a).unwrap().state() == BallState::Free {
+            poggle.update(delta);
+            ticks += 1;
+            assert!(ticks < 1000, "ball was never captured");
+        }
+        assert!(poggle.pegs[0].is_hit);
+
+        // Held in place for the whole capture
+        for _ in 1..Poggle::CANNON_HOLD_TICKS {
+            poggle.update(delta);
+            let ball = poggle.ball(a).unwrap();
+            assert!(matches!(
+                ball.state(),
+                BallState::Captured { by_peg: 0, .. }
+            ));
+            assert_eq!(ball.pos(), Point::new(100.0, 300.0));
+        }
+
+        poggle.update(delta);
+        let ball = poggle.ball(a).unwrap();
+        assert_eq!(ball.state(), BallState::Free);
+        assert!(ball.velocity().y < -490.0);
+        assert!(ball.velocity().x.abs() < 0.01);
+        assert!(!ball.has_gained_energy(poggle.energy_reference(), poggle.physics.gravity));
+
+        // Falling back onto the used cannon bounces off it instead of being captured
+        for _ in 0..400 {
+            poggle.update(delta);
+            if let Some(ball) = poggle.ball(a) {
+                assert_eq!(ball.state(), BallState::Free);
+            }
+        }
+    }
+
+    #[test]
+    fn test_free_fall_conserves_energy() {
+        let mut poggle = empty();
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+        // Thrown up hard enough to still be in the air after 600 ticks
+        let a = poggle.shoot(Point::new(100.0, 790.0), Point::new(50.0, -1800.0));
+        let start = poggle.ball(a).unwrap().total_energy(reference, gravity);
+
+        for _ in 0..600 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let energy = poggle.ball(a).unwrap().total_energy(reference, gravity);
+            assert!((energy - start).abs() <= start.abs() * 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_polygon_face_collision() {
+        let body = square(Point::new(100.0, 300.0), 20.0);
+        let ball = Ball::new(Point::new(100.0, 200.0), Point::new(0.0, 1000.0));
+
+        let collision = ball
+            .will_collide(&body, Point::zero(), Duration::from_millis(100))
+            .unwrap();
+        assert!(collision.point.distance_to(Point::new(100.0, 274.0)) < 1e-3);
+        assert!(collision.normal.distance_to(Point::new(0.0, -1.0)) < 1e-6);
+        assert!((collision.toi - 0.74).abs() < 1e-3);
+
+        // Too far away to get there this timestep
+        assert!(
+            ball.will_collide(&body, Point::zero(), Duration::from_millis(50))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_polygon_corner_collision() {
+        let body = square(Point::new(100.0, 300.0), 20.0);
+        let corner = Point::new(120.0, 280.0);
+        let ball = Ball::new(
+            corner + Point::new(50.0, -50.0),
+            Point::new(-1000.0, 1000.0),
+        );
+
+        let collision = ball
+            .will_collide(&body, Point::zero(), Duration::from_millis(100))
+            .unwrap();
+        assert!((collision.point.distance_to(corner) - Ball::RADIUS).abs() < 1e-3);
+
+        let diagonal = Point::new(1.0f32, -1.0).normalized();
+        assert!(collision.normal.distance_to(diagonal) < 1e-3);
+    }
+
+    #[test]
+    fn test_polygon_overlap_collision() {
+        let body = square(Point::new(100.0, 300.0), 20.0);
+
+        let into = Ball::new(Point::new(100.0, 278.0), Point::new(0.0, 100.0));
+        assert_eq!(
+            into.will_collide(&body, Point::zero(), Duration::from_millis(10)),
+            Some(Collision {
+                point: Point::new(100.0, 278.0),
+                toi: 0.0,
+                normal: Point::new(0.0, -1.0),
+            })
+        );
+
+        let away = Ball::new(Point::new(100.0, 278.0), Point::new(0.0, -100.0));
+        assert!(
+            away.will_collide(&body, Point::zero(), Duration::from_millis(10))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_polygon_nearly_vertical_collision() {
+        let body = square(Point::new(100.0, 300.0), 20.0);
+        let ball = Ball::new(Point::new(119.0, 200.0), Point::new(0.001, 1000.0));
+
+        let collision = ball
+            .will_collide(&body, Point::zero(), Duration::from_millis(100))
+            .unwrap();
+        assert!((collision.point.y - 274.0).abs() < 1e-3);
+        assert!((collision.point.x - 119.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_ball_bounces_off_polygon() {
+        let mut poggle = empty();
+        poggle.add_peg(Peg {
+            body: square(Point::new(100.0, 300.0), 20.0),
+            ..peg(Point::new(100.0, 300.0))
+        });
+        let a = poggle.shoot(Point::new(100.0, 200.0), Point::zero());
+
+        let mut bounced = false;
+        for _ in 0..200 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            assert!(ball.pos().y < 280.0);
+            bounced |= ball.velocity().y < 0.0;
+        }
+        assert!(bounced);
+        assert!(poggle.pegs[0].is_hit);
+    }
+
+    #[test]
+    fn test_ball_bounces_off_both_close_pegs() {
+        let mut poggle = empty();
+        // Only 6 px between the pegs, half a ball's width
+        poggle.add_peg(peg(Point::new(91.0, 400.0)));
+        poggle.add_peg(peg(Point::new(109.0, 400.0)));
+        let a = poggle.shoot(Point::new(101.0, 370.0), Point::new(0.0, 2500.0));
+
+        for _ in 0..10 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            assert!(ball.pos().y < 400.0, "ball slipped between the pegs");
+        }
+        assert!(poggle.pegs[0].is_hit);
+        assert!(poggle.pegs[1].is_hit);
+    }
+
+    #[test]
+    fn test_nearest_peg_is_hit_first() {
+        let near = peg(Point::new(100.0, 230.0));
+        let far = peg(Point::new(100.0, 260.0));
+        let ball = Ball::new(Point::new(100.0, 200.0), Point::new(0.0, 1000.0));
+
+        let time = Duration::from_millis(100);
+        let to_near = ball.will_collide(&near.body, Point::zero(), time).unwrap();
+        let to_far = ball.will_collide(&far.body, Point::zero(), time).unwrap();
+        assert!(to_near.toi < to_far.toi);
+        assert!((to_near.toi - 0.18).abs() < 1e-3);
+
+        // The far peg comes first in the list, but the near one must still win
+        let mut poggle = empty();
+        poggle.add_peg(far);
+        poggle.add_peg(near);
+        let a = poggle.shoot(Point::new(100.0, 200.0), Point::new(0.0, 2000.0));
+        for _ in 0..20 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert!(poggle.pegs[1].is_hit);
+        assert!(!poggle.pegs[0].is_hit);
+        assert!(poggle.ball(a).unwrap().velocity().y < 0.0);
+    }
+
+    #[test]
+    fn test_ball_bounces_off_wall() {
+        let mut poggle = empty();
+        let a = poggle.shoot(Point::new(640.0, 100.0), Point::new(-1500.0, 0.0));
+
+        let mut bounced = false;
+        for _ in 0..200 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            assert!(
+                ball.pos().x >= Ball::RADIUS
+                    && ball.pos().x <= sdl::WINDOW_WIDTH as f32 - Ball::RADIUS
+            );
+            if ball.velocity().x > 0.0 {
+                bounced = true;
+                assert!(ball.velocity().x <= 1500.0 * poggle.physics.elasticity + 1e-3);
+                break;
+            }
+        }
+        assert!(bounced);
+    }
+
+    #[test]
+    fn test_wall_collision_time() {
+        let ball = Ball::new(Point::new(100.0, 100.0), Point::new(-1000.0, 0.0));
+        let collision = ball
+            .will_hit_wall(Duration::from_millis(100), Walls::Solid)
+            .unwrap();
+        assert!((collision.toi - 0.94).abs() < 1e-4);
+        assert_eq!(collision.normal, Point::new(1.0, 0.0));
+        assert!(collision.point.distance_to(Point::new(Ball::RADIUS, 100.0)) < 1e-3);
+
+        assert!(
+            ball.will_hit_wall(Duration::from_millis(50), Walls::Solid)
+                .is_none()
+        );
+        assert!(
+            ball.will_hit_wall(Duration::from_millis(100), Walls::Open)
+                .is_none()
+        );
+    }
+
+    // Energy of the ball on the tick before and the tick after it first hits the peg
+    fn energy_around_bounce(start: Point<f32>) -> (f32, f32, Point<f32>) {
+        let mut poggle = empty();
+        poggle.add_peg(peg(Point::new(100.0, 300.0)));
+        let a = poggle.shoot(start, Point::zero());
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+
+        for _ in 0..200 {
+            let before = poggle.ball(a).unwrap().total_energy(reference, gravity);
+            poggle.update(Duration::from_secs(1) / 165);
+            if poggle.pegs[0].is_hit {
+                let ball = poggle.ball(a).unwrap();
+                return (
+                    before,
+                    ball.total_energy(reference, gravity),
+                    ball.velocity(),
+                );
+            }
+        }
+        panic!("ball never hit the peg");
+    }
+
+    #[test]
+    fn test_straight_bounce_loses_energy() {
+        let (before, after, velocity) = energy_around_bounce(Point::new(100.0, 200.0));
+        assert!(after <= before + before.abs() * Ball::ENERGY_TOLERANCE);
+        assert!(velocity.y < 0.0);
+        assert!(velocity.x.abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_glancing_bounce_loses_energy() {
+        // Lined up so the ball meets the peg 80 degrees away from its top
+        let offset = 12.0 * 80f32.to_radians().sin();
+        let (before, after, velocity) = energy_around_bounce(Point::new(100.0 + offset, 200.0));
+        assert!(after <= before + before.abs() * Ball::ENERGY_TOLERANCE);
+
+        // Barely deflected, so it keeps falling and gets pushed a little to the right
+        assert!(velocity.y > 0.0);
+        assert!(velocity.x > 0.0);
+    }
+
+    #[test]
+    fn test_powerup_peg_grants_powerup() {
+        let mut poggle = empty();
+        poggle.add_peg(Peg {
+            peg_type: PegType::PowerUp(PowerUp::MultiBall),
+            ..peg(Point::new(100.0, 300.0))
+        });
+        poggle.shoot(Point::new(100.0, 200.0), Point::zero());
+
+        for _ in 0..200 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        // Bouncing on the same peg again in the same shot doesn't grant another
+        assert_eq!(
+            poggle.powerups().iter().collect::<Vec<_>>(),
+            vec![(PowerUp::MultiBall, Allowance::Charges(1))]
+        );
+    }
+
+    #[test]
+    fn test_fast_ball_does_not_tunnel() {
+        // Start from a spread of distances so the ball ends up at every point of its
+        // step relative to the peg
+        for i in 0..50 {
+            let mut poggle = empty();
+            poggle.add_peg(peg(Point::new(100.0, 400.0)));
+            poggle.shoot(
+                Point::new(100.0, 100.0 + i as f32 * 0.7),
+                Point::new(0.0, 5000.0),
+            );
+
+            for _ in 0..20 {
+                poggle.update(Duration::from_secs(1) / 60);
+                if poggle.pegs[0].is_hit {
+                    break;
+                }
+            }
+            assert!(poggle.pegs[0].is_hit, "missed the peg starting {i}");
+        }
+    }
+
+    #[test]
+    fn test_wedged_ball_settles_on_top() {
+        let mut poggle = empty();
+        // Only 6 px between the pegs, half a ball's width
+        poggle.add_peg(peg(Point::new(91.0, 400.0)));
+        poggle.add_peg(peg(Point::new(109.0, 400.0)));
+        let a = poggle.shoot(Point::new(100.0, 400.0), Point::zero());
+
+        for _ in 0..300 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            assert!(ball.pos().y < 400.0, "ball went through the gap");
+        }
+
+        // Resting in the notch between the two pegs
+        let ball = poggle.ball(a).unwrap();
+        let resting = 400.0 - (144.0f32 - 81.0).sqrt();
+        assert!((ball.pos().x - 100.0).abs() < 0.5);
+        assert!((ball.pos().y - resting).abs() < 0.5);
+        assert!(ball.velocity().length() < 5.0);
+    }
+
+    #[test]
+    fn test_bounce_restitution() {
+        let normal = Point::new(-1.0f32, -1.0).normalized();
+
+        let mut elastic = Ball::new(Point::zero(), Point::new(300.0, 400.0));
+        elastic.bounce(normal, 1.0, 0.0);
+        assert!((elastic.velocity().length() - 500.0).abs() < 1e-3);
+        assert!(elastic.velocity().dot(normal) > 0.0);
+
+        let mut dead = Ball::new(Point::zero(), Point::new(300.0, 400.0));
+        dead.bounce(normal, 0.0, 0.0);
+        assert!(dead.velocity().dot(normal).abs() < 1e-3);
+        // Still slides along the surface
+        assert!(dead.velocity().length() > 50.0);
+
+        let mut away = Ball::new(Point::zero(), Point::new(-300.0, -400.0));
+        away.bounce(normal, 0.0, 1.0);
+        assert_eq!(away.velocity(), Point::new(-300.0, -400.0));
+    }
+
+    #[test]
+    fn test_bounce_friction() {
+        let normal = Point::new(0.0, -1.0);
+        let mut ball = Ball::new(Point::zero(), Point::new(200.0, 100.0));
+        ball.bounce(normal, 0.5, 0.25);
+        assert!(ball.velocity().distance_to(Point::new(150.0, -50.0)) < 1e-3);
+    }
+
+    #[test]
+    fn test_dead_peg_stops_bounce() {
+        let mut poggle = empty();
+        poggle.add_pegs(sturdy(Poggle::generate_grid(
+            Point::new(100.0, 300.0),
+            Point::new(100.0, 300.0),
+            10.0,
+            0.0,
+            0.0,
+        )));
+        let a = poggle.shoot(Point::new(100.0, 200.0), Point::zero());
+
+        for _ in 0..200 {
+            poggle.update(Duration::from_secs(1) / 165);
+            assert!(poggle.ball(a).unwrap().velocity().y > -1.0);
+        }
+        assert!(poggle.pegs[0].is_hit);
+    }
+
+    #[test]
+    fn test_balls_pass_through_by_default() {
+        let mut poggle = empty();
+        let a = poggle.shoot(Point::new(100.0, 100.0), Point::new(100.0, 0.0));
+        let b = poggle.shoot(Point::new(110.0, 100.0), Point::new(-100.0, 0.0));
+        poggle.update(Duration::from_secs(1) / 165);
+
+        assert!(poggle.ball(a).unwrap().velocity().x > 0.0);
+        assert!(poggle.ball(b).unwrap().velocity().x < 0.0);
+    }
+
+    #[test]
+    fn test_head_on_ball_collision() {
+        let mut poggle = empty();
+        poggle.set_ball_collisions(true);
+        let a = poggle.shoot(Point::new(100.0, 100.0), Point::new(100.0, 0.0));
+        let b = poggle.shoot(Point::new(110.0, 100.0), Point::new(-100.0, 0.0));
+        poggle.update(Duration::from_secs(1) / 165);
+
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+        let (a, b) = (poggle.ball(a).unwrap(), poggle.ball(b).unwrap());
+        assert!((a.velocity().x + 100.0 * poggle.physics.elasticity).abs() < 1e-3);
+        assert!((b.velocity().x - 100.0 * poggle.physics.elasticity).abs() < 1e-3);
+        assert!(a.pos().distance_to(b.pos()) >= 2.0 * Ball::RADIUS - 1e-3);
+        assert!(
+            !a.has_gained_energy(reference, gravity) && !b.has_gained_energy(reference, gravity)
+        );
+    }
+
+    #[test]
+    fn test_ball_clump_separates() {
+        let mut poggle = empty();
+        poggle.set_ball_collisions(true);
+        for i in 0..6 {
+            poggle.shoot(Point::new(300.0 + i as f32, 300.0), Point::zero());
+        }
+
+        for _ in 0..5 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        for (i, a) in poggle.balls.iter().enumerate() {
+            for b in &poggle.balls[i + 1..] {
+                assert!(a.pos().distance_to(b.pos()) > 2.0 * Ball::RADIUS * 0.9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_spin_curves_without_gaining_speed() {
+        let mut ball = Ball::new(Point::zero(), Point::new(1000.0, 0.0));
+        ball.spin = 50.0;
+        for _ in 0..165 {
+            ball.apply_spin(1.0 / 165.0);
+        }
+
+        assert!((ball.velocity().length() - 1000.0).abs() < 0.1);
+        // Clockwise spin curves the ball clockwise on screen, which is downwards
+        assert!(ball.velocity().y > 0.0);
+        assert!((ball.spin - 50.0 * (1.0 - Ball::SPIN_DAMPING)).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_friction_imparts_spin() {
+        // Sliding to the right along the top of a surface rolls clockwise
+        let mut ball = Ball::new(Point::zero(), Point::new(200.0, 100.0));
+        ball.bounce(Point::new(0.0, -1.0), 0.5, 0.5);
+        assert!(ball.spin > 0.0);
+
+        let mut smooth = Ball::new(Point::zero(), Point::new(200.0, 100.0));
+        smooth.bounce(Point::new(0.0, -1.0), 0.5, 0.0);
+        assert_eq!(smooth.spin, 0.0);
+    }
+
+    #[test]
+    fn test_spinning_bounces_do_not_gain_energy() {
+        let mut poggle = empty();
+        poggle.add_pegs(sturdy(Poggle::generate_grid(
+            Point::new(100.0, 300.0),
+            Point::new(400.0, 600.0),
+            50.0,
+            PhysicsConfig::default().elasticity,
+            0.3,
+        )));
+        for i in 0..20 {
+            poggle.shoot(
+                Point::new(90.0 + i as f32 * 17.0, 200.0),
+                Point::new(150.0, 0.0),
+            );
+        }
+
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+        for _ in 0..600 {
+            poggle.update(Duration::from_secs(1) / 165);
+            assert!(
+                poggle
+                    .balls
+                    .iter()
+                    .all(|b| !b.has_gained_energy(reference, gravity))
+            );
+        }
+        assert!(poggle.pegs.iter().any(|p| p.friction > 0.0 && p.is_hit));
+    }
+
+    // Debug and release builds have to agree on this. Rust never contracts a * b + c into
+    // a fused multiply-add or reorders float operations on its own, whatever the opt
+    // level, so the things that could make them differ are explicit `mul_add` calls
+    // (there are none) and the platform's sqrt/sin/cos/powf, which both profiles share.
+    // If this changes on purpose, the failure message has the new value.
+    // scripts/check_determinism.sh runs it in both profiles.
+    #[test]
+    fn test_determinism() {
+        const GOLDEN: u64 = 0xd677d9b00c1c968c;
+
+        // The demo board with every 20th ball, to keep debug builds quick
+        let mut poggle = Poggle::default();
+        poggle
+            .balls
+            .retain(|ball| ball.handle.is_some_and(|BallHandle(h)| h % 20 == 0));
+        let mut combined = 0u64;
+        for tick in 0..10_000 {
+            poggle.update(Duration::from_secs(1) / 165);
+            if tick % 100 == 0 {
+                combined = combined.rotate_left(5) ^ poggle.state_hash();
+            }
+        }
+        assert_eq!(combined, GOLDEN, "determinism hash is {combined:#018x}");
+    }
+
+    fn big_peg(pos: Point<f32>) -> Peg {
+        Peg {
+            body: Body {
+                pos,
+                shape: Shape::Circle { radius: 40.0 },
+            },
+            ..peg(pos)
+        }
+    }
+
+    #[test]
+    fn test_ball_comes_to_rest_on_peg() {
+        let mut poggle = empty();
+        poggle.add_peg(big_peg(Point::new(300.0, 400.0)));
+        let a = poggle.shoot(
+            Point::new(300.0, 400.0 - 40.0 - Ball::RADIUS - 2.0),
+            Point::zero(),
+        );
+
+        for _ in 0..165 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        let ball = poggle.ball(a).unwrap();
+        assert!(ball.velocity().length() < 1.0);
+        assert!(ball.pos().distance_to(Point::new(300.0, 354.0)) < 0.5);
+    }
+
+    #[test]
+    fn test_resting_ball_rolls_off() {
+        let mut poggle = empty();
+        poggle.add_peg(big_peg(Point::new(300.0, 400.0)));
+        let a = poggle.shoot(
+            Point::new(305.0, 400.0 - 40.0 - Ball::RADIUS - 2.0),
+            Point::zero(),
+        );
+
+        // Rolls around the peg and falls off to the right
+        for _ in 0..330 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            if ball.pos().y > 400.0 {
+                assert!(ball.pos().x > 340.0);
+                return;
+            }
+        }
+        panic!("ball never rolled off");
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with cargo test --release -- --ignored --nocapture bench"]
+    fn bench_peg_broad_phase() {
+        let board = |brute_force: bool| {
+            let mut poggle = empty();
+            poggle.add_pegs(Poggle::generate_grid(
+                Point::new(20.0, 100.0),
+                Point::new(1260.0, 780.0),
+                29.0,
+                PhysicsConfig::default().elasticity,
+                0.0,
+            ));
+            if brute_force {
+                // A single cell holding every peg is the same as checking all of them
+                poggle.peg_grid =
+                    SpatialHash::with_cell_size(poggle.pegs.iter().map(|peg| &peg.body), f32::MAX);
+            }
+            for i in 0..500 {
+                poggle.shoot(
+                    Point::new(
+                        20.0 + (i % 100) as f32 * 12.4,
+                        20.0 + (i / 100) as f32 * 12.0,
+                    ),
+                    Point::new(((i * 37) % 200) as f32 - 100.0, 0.0),
+                );
+            }
+            poggle
+        };
+
+        for brute_force in [true, false] {
+            let mut poggle = board(brute_force);
+            let start = std::time::Instant::now();
+            for _ in 0..165 {
+                poggle.update(Duration::from_secs(1) / 165);
+            }
+            println!(
+                "{} pegs, {}: {:?} per update",
+                poggle.pegs.len(),
+                if brute_force {
+                    "every peg"
+                } else {
+                    "spatial hash"
+                },
+                start.elapsed() / 165
+            );
+        }
+    }
+
+    #[test]
+    fn test_motion_velocity_matches_position() {
+        let motions = [
+            Motion::Linear {
+                from: Point::new(100.0, 100.0),
+                to: Point::new(300.0, 200.0),
+                period: 2.0,
+            },
+            Motion::Circular {
+                center: Point::new(300.0, 300.0),
+                radius: 50.0,
+                period: 3.0,
+            },
+        ];
+        for motion in motions {
+            for i in 0..20 {
+                let t = i as f32 * 0.17;
+                let h = 0.001;
+                let estimate = (motion.position_at(t + h) - motion.position_at(t - h)) / (2.0 * h);
+                assert!(estimate.distance_to(motion.velocity_at(t)) < 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_moving_peg_pushes_resting_ball() {
+        let mut poggle = empty();
+        poggle.add_peg(Peg {
+            body: square(Point::new(300.0, 500.0), 100.0),
+            ..peg(Point::zero())
+        });
+        // Sweeps along the floor through where the ball is sitting
+        poggle.add_peg(Peg {
+            motion: Some(Motion::Linear {
+                from: Point::new(200.0, 360.0),
+                to: Point::new(400.0, 360.0),
+                period: 2.0,
+            }),
+            ..big_peg(Point::new(200.0, 360.0))
+        });
+        let a = poggle.shoot(Point::new(300.0, 400.0 - Ball::RADIUS), Point::zero());
+
+        for _ in 0..165 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            let gap = ball.pos().distance_to(poggle.pegs[1].body.pos) - 40.0 - Ball::RADIUS;
+            assert!(gap > -0.5, "ball is {} inside the peg", -gap);
+            assert!(!ball.gained_energy);
+        }
+        let ball = poggle.ball(a).unwrap();
+        assert!(ball.pos().x > 400.0);
+        assert!(ball.velocity().x > 100.0);
+    }
+
+    #[test]
+    fn test_spinning_blade_carries_ball() {
+        let mut poggle = empty();
+        let center = Point::new(300.0, 400.0);
+        poggle.add_peg(Peg {
+            body: Body {
+                pos: center,
+                shape: Shape::Polygon {
+                    points: vec![
+                        Point::new(-80.0, -5.0),
+                        Point::new(80.0, -5.0),
+                        Point::new(80.0, 5.0),
+                        Point::new(-80.0, 5.0),
+                    ],
+                    rotation: 0.0,
+                    rounding: 0.0,
+                },
+            },
+            // Lifts the left end
+            angular_velocity: 3.0,
+            ..peg(center)
+        });
+        let a = poggle.shoot(Point::new(240.0, 400.0 - 5.0 - Ball::RADIUS), Point::zero());
+
+        for _ in 0..40 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            let blade = &poggle.pegs[0].body;
+            assert!(!blade.extend(Ball::RADIUS - 0.5).contains(ball.pos()));
+            let Shape::Polygon { rotation, .. } = blade.shape else {
+                unreachable!()
+            };
+            let up = Point::new(0.0, -1.0).rotated(rotation);
+            assert!(
+                center.to(ball.pos()).dot(up) > 0.0,
+                "ball went through the blade"
+            );
+        }
+        // Still on the blade and going up with it
+        let ball = poggle.ball(a).unwrap();
+        assert!(ball.velocity().y < -100.0);
+        assert!(ball.pos().y < 380.0);
+    }
+
+    fn capsule(a: Point<f32>, b: Point<f32>) -> Body {
+        Body {
+            pos: Point::zero(),
+            shape: Shape::Capsule { a, b, radius: 5.0 },
+        }
+    }
+
+    #[test]
+    fn test_capsule_end_cap() {
+        let body = capsule(Point::new(100.0, 100.0), Point::new(200.0, 100.0));
+
+        // Comes down just past the end, so it only clips the round cap
+        let ball = Ball::new(Point::new(205.0, 50.0), Point::new(0.0, 100.0));
+        let collision = ball
+            .will_collide(&body, Point::zero(), Duration::from_secs(1))
+            .unwrap();
+        let distance = collision.point.distance_to(Point::new(200.0, 100.0));
+        assert!((distance - 5.0 - Ball::RADIUS).abs() < 0.01);
+        assert!(collision.normal.x > 0.0 && collision.normal.y < 0.0);
+    }
+
+    #[test]
+    fn test_capsule_parallel_movement() {
+        let body = capsule(Point::new(100.0, 100.0), Point::new(200.0, 100.0));
+
+        // Straight along the segment into the end
+        let ball = Ball::new(Point::new(300.0, 100.0), Point::new(-100.0, 0.0));
+        let collision = ball
+            .will_collide(&body, Point::zero(), Duration::from_secs(1))
+            .unwrap();
+        assert!(collision.point.distance_to(Point::new(211.0, 100.0)) < 0.01);
+        assert!(collision.normal.distance_to(Point::new(1.0, 0.0)) < 1e-4);
+
+        // Alongside and past it without touching
+        let ball = Ball::new(Point::new(300.0, 88.0), Point::new(-300.0, 0.0));
+        assert!(
+            ball.will_collide(&body, Point::zero(), Duration::from_secs(1))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_ball_rolls_down_ramp() {
+        let mut poggle = empty();
+        poggle.add_peg(Peg {
+            body: capsule(Point::new(200.0, 300.0), Point::new(400.0, 400.0)),
+            ..peg(Point::zero())
+        });
+        let a = poggle.shoot(Point::new(220.0, 280.0), Point::zero());
+
+        for _ in 0..330 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            // Never ends up under the ramp
+            let on_ramp = Point::new(200.0, 300.0).to(ball.pos());
+            let side = on_ramp.x * 100.0 - on_ramp.y * 200.0;
+            assert!(side > 0.0 || ball.pos().x > 405.0 + Ball::RADIUS);
+        }
+        let ball = poggle.ball(a).unwrap();
+        assert!(ball.pos().x > 400.0);
+        assert!(ball.velocity().x > 0.0);
+    }
+
+    #[test]
+    fn test_brick_corner_bounce() {
+        let mut poggle = empty();
+        poggle.add_peg(Peg {
+            body: Body {
+                pos: Point::new(300.0, 400.0),
+                shape: Shape::Rect {
+                    half_extents: Point::new(30.0, 10.0),
+                    rounding: 0.0,
+                },
+            },
+            ..peg(Point::zero())
+        });
+        // Falls on the very corner, slightly outside of it
+        let a = poggle.shoot(Point::new(332.0, 300.0), Point::zero());
+
+        for _ in 0..100 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        // A face bounce would send it straight back up
+        let ball = poggle.ball(a).unwrap();
+        assert!(ball.velocity().x > 50.0);
+    }
+
+    // Sits still with the opening 60 wide at y = 700
+    fn still_bucket(x: f32) -> Bucket {
+        let pos = Point::new(x, 700.0);
+        Bucket::new(
+            Motion::Linear {
+                from: pos,
+                to: pos,
+                period: 1.0,
+            },
+            60.0,
+            30.0,
+        )
+    }
+
+    #[test]
+    fn test_bucket_catches_only_from_above() {
+        let bucket = still_bucket(300.0);
+
+        assert!(bucket.catches(Point::new(300.0, 690.0), Point::new(310.0, 705.0)));
+        assert!(!bucket.catches(Point::new(400.0, 690.0), Point::new(400.0, 705.0)));
+        // Coming back out, and skimming across just inside the rim
+        assert!(!bucket.catches(Point::new(300.0, 705.0), Point::new(300.0, 690.0)));
+        assert!(!bucket.catches(Point::new(250.0, 701.0), Point::new(350.0, 701.0)));
+    }
+
+    #[test]
+    fn test_ball_caught_by_bucket() {
+        let mut poggle = empty();
+        poggle.bucket = Some(still_bucket(300.0));
+        let a = poggle.shoot(Point::new(300.0, 600.0), Point::zero());
+        // Lands on top of the right wall
+        let b = poggle.shoot(Point::new(334.0, 600.0), Point::zero());
+
+        for _ in 0..120 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert!(poggle.ball(a).is_none());
+        assert_eq!(poggle.caught(), 1);
+        let ball = poggle.ball(b).unwrap();
+        assert!(ball.pos().y < 700.0 - Ball::RADIUS);
+        assert!(ball.velocity().y < 0.0);
+    }
+
+    #[test]
+    fn test_flipper_swings_up_and_back() {
+        let mut poggle = empty();
+        poggle.powerups.grant(PowerUp::Flippers);
+        let (rest, raised) = (poggle.flippers[0].rest, poggle.flippers[0].raised);
+
+        poggle.set_flipper_input(true, false);
+        for _ in 0..20 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert!((poggle.flippers[0].angle - raised).abs() < 1e-4);
+        assert_eq!(poggle.flippers[1].angle, poggle.flippers[1].rest);
+
+        poggle.set_flipper_input(false, false);
+        for _ in 0..20 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert!((poggle.flippers[0].angle - rest).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_flippers_only_there_with_powerup() {
+        let drop = |poggle: &mut Poggle| {
+            // Right above the middle of the left flipper
+            let a = poggle.shoot(Point::new(190.0, 650.0), Point::zero());
+            for _ in 0..100 {
+                poggle.update(Duration::from_secs(1) / 165);
+            }
+            poggle.ball(a).map_or(f32::INFINITY, |ball| ball.pos().y)
+        };
+
+        assert!(drop(&mut empty()) > 750.0);
+
+        let mut poggle = empty();
+        poggle.powerups.grant(PowerUp::Flippers);
+        assert!(drop(&mut poggle) < 730.0);
+    }
+
+    #[test]
+    fn test_flipper_launches_resting_ball() {
+        let mut poggle = empty();
+        poggle.powerups.grant(PowerUp::Flippers);
+        let flipper = &poggle.flippers[0];
+        let up = Point::from(PolarPoint::new(flipper.angle - consts::FRAC_PI_2, 1.0));
+        let contact = flipper.pivot + Point::from(PolarPoint::new(flipper.angle, 100.0));
+        let a = poggle.shoot(
+            contact + up * (Flipper::RADIUS + Ball::RADIUS),
+            Point::zero(),
+        );
+
+        poggle.set_flipper_input(true, false);
+        for _ in 0..8 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            let body = poggle.flippers[0].body();
+            assert!(!body.extend(Ball::RADIUS - 0.5).contains(ball.pos()));
+            assert!(!ball.gained_energy);
+        }
+        let ball = poggle.ball(a).unwrap();
+        assert!(ball.velocity().y < -500.0);
+        assert!(ball.pos().y < contact.y);
+    }
+
+    #[test]
+    fn test_gravity_changes_the_shot() {
+        let shot = |gravity: Point<f32>| {
+            let mut poggle = empty();
+            poggle.physics.gravity = gravity;
+            let a = poggle.shoot(Point::new(100.0, 100.0), Point::new(250.0, 0.0));
+            for _ in 0..165 {
+                poggle.update(Duration::from_secs(1) / 165);
+            }
+            let reference = poggle.energy_reference();
+            let ball = poggle.ball(a).unwrap();
+            assert!(!ball.has_gained_energy(reference, gravity));
+            ball.pos()
+        };
+
+        // Same sideways speed either way, only the drop differs
+        let normal = shot(PhysicsConfig::default().gravity);
+        let floaty = shot(Point::new(0.0, 100.0));
+        assert!((normal.x - floaty.x).abs() < 1e-3);
+        assert!(floaty.y < normal.y - 200.0);
+    }
+
+    #[test]
+    fn test_open_walls_let_balls_out() {
+        let mut poggle = empty();
+        poggle.physics.walls = Walls::Open;
+        let a = poggle.shoot(Point::new(100.0, 100.0), Point::new(-1500.0, 0.0));
+        for _ in 0..20 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert!(poggle.ball(a).unwrap().pos().x < 0.0);
+    }
+
+    #[test]
+    fn test_magnet_bends_passing_ball() {
+        let pass = |offset: f32| {
+            let mut poggle = empty();
+            poggle.physics.gravity = Point::zero();
+            poggle.add_peg(Peg {
+                peg_type: PegType::Magnet {
+                    strength: 2.0e6,
+                    range: 100.0,
+                },
+                ..peg(Point::new(300.0, 300.0))
+            });
+            let a = poggle.shoot(Point::new(100.0, 300.0 + offset), Point::new(200.0, 0.0));
+            let reference = poggle.energy_reference();
+            for _ in 0..330 {
+                poggle.update(Duration::from_secs(1) / 165);
+                let ball = poggle.ball(a).unwrap();
+                assert!(!ball.has_gained_energy(reference, Point::zero()));
+            }
+            assert!(!poggle.pegs[0].is_hit);
+            poggle.ball(a).unwrap().velocity()
+        };
+
+        let inside = pass(95.0);
+        let outside = pass(105.0);
+        assert_eq!(outside, Point::new(200.0, 0.0));
+        assert!(inside.y < -10.0);
+    }
+
+    #[test]
+    fn test_mirror_world_waits_for_end_of_shot() {
+        let mut poggle = empty();
+        poggle.add_pegs([
+            peg(Point::new(100.0, 300.0)),
+            Peg {
+                motion: Some(Motion::Linear {
+                    from: Point::new(200.0, 400.0),
+                    to: Point::new(300.0, 400.0),
+                    period: 2.0,
+                }),
+                ..peg(Point::new(200.0, 400.0))
+            },
+        ]);
+        poggle.powerups.grant(PowerUp::MirrorWorld);
+        let a = poggle.shoot(Point::new(1000.0, 100.0), Point::new(50.0, 0.0));
+
+        let delta = Duration::from_secs(1) / 165;
+        for _ in 0..10 {
+            poggle.update(delta);
+            assert_eq!(poggle.pegs[0].body.pos, Point::new(100.0, 300.0));
+            assert!(poggle.ball(a).unwrap().pos().x > 1000.0);
+        }
+        poggle.remove_ball(a);
+
+        assert!(!poggle.powerups.is_active(PowerUp::MirrorWorld));
+        assert_eq!(poggle.pegs[0].body.pos, Point::new(1180.0, 300.0));
+        assert_eq!(
+            poggle.pegs[1].motion,
+            Some(Motion::Linear {
+                from: Point::new(1080.0, 400.0),
+                to: Point::new(980.0, 400.0),
+                period: 2.0,
+            })
+        );
+
+        // Pegs can't be hit while they're sliding over
+        assert!(poggle.is_mirroring());
+        let b = poggle.shoot(Point::new(1180.0, 280.0), Point::zero());
+        for _ in 0..40 {
+            poggle.update(delta);
+        }
+        assert!(!poggle.pegs[0].is_hit);
+        poggle.remove_ball(b);
+
+        for _ in 0..170 {
+            poggle.update(delta);
+        }
+        assert!(!poggle.is_mirroring());
+        poggle.shoot(Point::new(1180.0, 280.0), Point::zero());
+        for _ in 0..40 {
+            poggle.update(delta);
+        }
+        assert!(poggle.pegs[0].is_hit);
+    }
+
+    #[test]
+    fn test_mirroring_twice_restores_board() {
+        let mut poggle = empty();
+        poggle.add_pegs([
+            Peg {
+                body: Body {
+                    pos: Point::new(300.0, 300.0),
+                    shape: Shape::Polygon {
+                        points: vec![
+                            Point::new(-20.0, -5.0),
+                            Point::new(25.0, -5.0),
+                            Point::new(0.0, 10.0),
+                        ],
+                        rotation: 0.4,
+                        rounding: 0.0,
+                    },
+                },
+                angular_velocity: 2.0,
+                ..peg(Point::zero())
+            },
+            Peg {
+                motion: Some(Motion::Circular {
+                    center: Point::new(500.0, 500.0),
+                    radius: 40.0,
+                    period: 3.0,
+                }),
+                body: Body {
+                    pos: Point::new(500.0, 500.0),
+                    shape: Shape::Capsule {
+                        a: Point::new(-10.0, 0.0),
+                        b: Point::new(30.0, 5.0),
+                        radius: 5.0,
+                    },
+                },
+                ..peg(Point::zero())
+            },
+            Peg {
+                peg_type: PegType::Cannon {
+                    direction: 0.5,
+                    speed: 500.0,
+                },
+                ..peg(Point::new(200.0, 100.0))
+            },
+        ]);
+        let outline = |poggle: &Poggle| -> Vec<Vec<Point<f32>>> {
+            poggle.pegs.iter().map(|peg| peg.body.vertices()).collect()
+        };
+        let before = outline(&poggle);
+
+        poggle.powerups.grant(PowerUp::MirrorWorld);
+        poggle.finish_shot();
+        // The circling peg has to stay on its mirrored path
+        let Some(motion) = poggle.pegs[1].motion else {
+            panic!("motion went missing");
+        };
+        for t in [0.0, 0.7, 1.9] {
+            let forward = Motion::Circular {
+                center: Point::new(500.0, 500.0),
+                radius: 40.0,
+                period: 3.0,
+            }
+            .position_at(t);
+            let mirrored = motion.position_at(t);
+            assert!(mirrored.distance_to(Point::new(1280.0 - forward.x, forward.y)) < 1e-3);
+        }
+
+        poggle.powerups.grant(PowerUp::MirrorWorld);
+        poggle.finish_shot();
+        for (a, b) in before
+            .iter()
+            .flatten()
+            .zip(outline(&poggle).iter().flatten())
+        {
+            assert!(a.distance_to(*b) < 1e-3);
+        }
+        assert_eq!(poggle.pegs[0].angular_velocity, 2.0);
+        assert!(matches!(
+            poggle.pegs[2].peg_type,
+            PegType::Cannon { direction, .. } if (direction - 0.5).abs() < 1e-6
+        ));
+    }
+
+    fn zone(center: Point<f32>, half_extents: Point<f32>, acceleration: Point<f32>) -> ForceZone {
+        ForceZone::new(
+            Body {
+                pos: center,
+                shape: Shape::Rect {
+                    half_extents,
+                    rounding: 0.0,
+                },
+            },
+            acceleration,
+        )
+    }
+
+    #[test]
+    fn test_overlapping_zones_add_up() {
+        let mut poggle = empty();
+        // Each one takes away half of gravity, and they overlap around x = 300
+        let half = Point::new(0.0, -PhysicsConfig::default().gravity.y / 2.0);
+        poggle.add_zone(zone(
+            Point::new(250.0, 300.0),
+            Point::new(100.0, 200.0),
+            half,
+        ));
+        poggle.add_zone(zone(
+            Point::new(350.0, 300.0),
+            Point::new(100.0, 200.0),
+            half,
+        ));
+        let both = poggle.shoot(Point::new(300.0, 300.0), Point::zero());
+        let one = poggle.shoot(Point::new(200.0, 300.0), Point::zero());
+
+        for _ in 0..50 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        let one_fall = Point::new(0.0, 275.0 * 50.0 / 165.0);
+        assert!(poggle.ball(both).unwrap().velocity().length() < 1e-3);
+        assert!(poggle.ball(one).unwrap().velocity().distance_to(one_fall) < 1e-2);
+    }
+
+    #[test]
+    fn test_updraft_energy_is_not_flagged() {
+        let mut poggle = empty();
+        poggle.add_zone(zone(
+            Point::new(300.0, 500.0),
+            Point::new(100.0, 100.0),
+            Point::new(300.0, -1500.0),
+        ));
+        let a = poggle.shoot(Point::new(300.0, 300.0), Point::zero());
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+
+        let mut lifted = false;
+        for _ in 0..300 {
+            poggle.update(Duration::from_secs(1) / 165);
+            let Some(ball) = poggle.ball(a) else { break };
+            lifted |= ball.velocity().y < -100.0;
+            assert!(!ball.has_gained_energy(reference, gravity));
+        }
+        assert!(lifted);
+    }
+
+    // Speed of the ball on the tick before and the tick after it first hits the bumper
+    fn speeds_around_bumper(boost: f32) -> (f32, f32) {
+        let mut poggle = empty();
+        poggle.add_peg(Peg {
+            peg_type: PegType::Bumper { boost },
+            ..peg(Point::new(100.0, 300.0))
+        });
+        let a = poggle.shoot(Point::new(100.0, 200.0), Point::zero());
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+
+        for _ in 0..200 {
+            let before = poggle.ball(a).unwrap().velocity().length();
+            poggle.update(Duration::from_secs(1) / 165);
+            let ball = poggle.ball(a).unwrap();
+            assert!(!ball.has_gained_energy(reference, gravity));
+            if poggle.pegs[0].is_hit {
+                assert_eq!(poggle.pegs[0].last_hit_tick, Some(poggle.tick - 1));
+                return (before, ball.velocity().length());
+            }
+        }
+        panic!("ball never hit the bumper");
+    }
+
+    #[test]
+    fn test_bumper_speeds_ball_up() {
+        let (before, after) = speeds_around_bumper(200.0);
+        assert!(after > before + 100.0);
+
+        // However hard it kicks, the ball only gets so fast
+        let (_, after) = speeds_around_bumper(10_000.0);
+        assert!(after <= Peg::BUMPER_MAX_SPEED + 10.0);
+        assert!(after > 1000.0);
+    }
+
+    fn portal(pos: Point<f32>, channel: u8) -> Peg {
+        Peg {
+            peg_type: PegType::Portal { channel },
+            ..peg(pos)
+        }
+    }
+
+    #[test]
+    fn test_portal_teleports_ball() {
+        let mut poggle = empty();
+        poggle.add_pegs([
+            portal(Point::new(100.0, 300.0), 0),
+            portal(Point::new(600.0, 200.0), 0),
+        ]);
+        let a = poggle.shoot(Point::new(100.0, 250.0), Point::zero());
+        let reference = poggle.energy_reference();
+        let gravity = poggle.physics.gravity;
+
+        for _ in 0..100 {
+            poggle.update(Duration::from_secs(1) / 165);
+            if poggle.pegs[0].is_hit {
+                break;
+            }
+        }
+        let ball = poggle.ball(a).unwrap();
+        assert!((ball.pos().x - 600.0).abs() < 1e-3);
+        assert!(ball.pos().y > 212.0 && ball.pos().y < 216.0);
+        assert!(ball.velocity().x.abs() < 1e-3 && ball.velocity().y > 0.0);
+        assert!(!ball.has_gained_energy(reference, gravity));
+        assert!(!poggle.pegs[1].is_hit);
+    }
+
+    #[test]
+    fn test_portal_cooldown() {
+        let mut ball = Ball::new(Point::zero(), Point::zero());
+        ball.portal_cooldowns.push((3, 10));
+        assert!(!ball.portal_ready(3, 5));
+        assert!(ball.portal_ready(4, 5));
+        assert!(ball.portal_ready(3, 10));
+        assert!(ball.portal_cooldowns.is_empty());
+    }
+
+    #[test]
+    fn test_unpaired_portals() {
+        let mut poggle = empty();
+        // By hand, since adding them like this is a mistake
+        poggle.pegs.extend([
+            portal(Point::new(100.0, 300.0), 1),
+            portal(Point::new(200.0, 300.0), 2),
+            portal(Point::new(300.0, 300.0), 2),
+        ]);
+        poggle.rebuild_peg_grid();
+        poggle.link_portals();
+        assert_eq!(poggle.portals, vec![(1, 2)]);
+        assert!(
+            poggle
+                .check_portals()
+                .unwrap_err()
+                .contains("channel 1 has 1")
+        );
+
+        // A lone portal is just a peg
+        let a = poggle.shoot(Point::new(100.0, 250.0), Point::zero());
+        for _ in 0..100 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert!(poggle.pegs[0].is_hit);
+        assert!((poggle.ball(a).unwrap().pos().x - 100.0).abs() < 1e-3);
+
+        poggle.add_peg(portal(Point::new(400.0, 300.0), 1));
+        assert!(poggle.check_portals().is_ok());
+        assert_eq!(poggle.portals, vec![(0, 3), (1, 2)]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "portal channel 3 has 1 portals")]
+    fn test_adding_lone_portal_is_reported() {
+        let mut poggle = empty();
+        poggle.add_pegs([
+            portal(Point::new(100.0, 300.0), 2),
+            portal(Point::new(200.0, 300.0), 2),
+            portal(Point::new(300.0, 300.0), 3),
+        ]);
+    }
+
+    #[test]
+    fn test_breakable_peg_takes_three_hits() {
+        let mut poggle = empty();
+        poggle.add_pegs([
+            Peg {
+                hits_remaining: 3,
+                ..peg(Point::new(100.0, 300.0))
+            },
+            // Pushed down an index once the breakable one is gone
+            peg(Point::new(400.0, 300.0)),
+        ]);
+        let a = poggle.shoot(Point::new(100.0, 200.0), Point::zero());
+
+        let mut hits = Vec::new();
+        for _ in 0..1000 {
+            let before = poggle.pegs[0].hits_remaining;
+            poggle.update(Duration::from_secs(1) / 165);
+            if poggle.pegs.len() == 1 {
+                hits.push(0);
+                break;
+            }
+            let after = poggle.pegs[0].hits_remaining;
+            if after != before {
+                // Each hit is a separate bounce
+                assert!(poggle.ball(a).unwrap().velocity().y < 0.0);
+                hits.push(after);
+            }
+        }
+        assert_eq!(hits, vec![2, 1, 0]);
+        // Bounced off on the last hit too, before the peg went
+        assert!(poggle.ball(a).unwrap().velocity().y < 0.0);
+        assert_eq!(poggle.pegs[0].body.pos, Point::new(400.0, 300.0));
+        assert_eq!(poggle.peg_grid.len(), 1);
+    }
+
+    #[test]
+    fn test_default_pegs_break_on_first_bounce() {
+        let one_peg = || {
+            let mut poggle = empty();
+            let grid_peg = Point::new(100.0, 300.0);
+            poggle.add_pegs(Poggle::generate_grid(
+                grid_peg,
+                grid_peg,
+                10.0,
+                PhysicsConfig::default().elasticity,
+                0.0,
+            ));
+            poggle
+        };
+
+        let mut poggle = one_peg();
+        poggle.shoot(Point::new(100.0, 200.0), Point::zero());
+        for _ in 0..100 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert!(poggle.pegs.is_empty());
+
+        // Set down right on top of it, too slow to count as a bounce
+        let mut poggle = one_peg();
+        poggle.shoot(Point::new(100.0, 300.0 - 12.05), Point::zero());
+        for _ in 0..20 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert!(poggle.pegs[0].is_hit);
+        assert_eq!(poggle.pegs[0].hits_remaining, 1);
+    }
+
+    #[test]
+    fn test_idle_ticks_do_not_end_shots() {
+        let mut poggle = empty();
+        poggle.powerups.grant(PowerUp::SuperGuide);
+        let handle = poggle.shoot(Point::new(100.0, 100.0), Point::zero());
+        poggle.remove_ball(handle);
+        for _ in 0..10 {
+            poggle.update(Duration::from_secs(1) / 165);
+        }
+        assert_eq!(
+            poggle.powerups().iter().collect::<Vec<_>>(),
+            vec![(PowerUp::SuperGuide, Allowance::Shots(2))]
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use sdl2::{pixels::Color, rect::Rect};
+
+use crate::{poggle::PowerUp, sdl::Render};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Allowance {
+    // Used up one at a time, granting again adds another
+    Charges(u32),
+    // Lasts until this many shots have finished, granting again starts over
+    Shots(u32),
+}
+
+impl PowerUp {
+    // What a single grant of the powerup is worth
+    fn allowance(self) -> Allowance {
+        match self {
+            PowerUp::MultiBall
+            | PowerUp::SpookyBall
+            | PowerUp::Zen
+            | PowerUp::Explosion
+            | PowerUp::MagicWheel
+            | PowerUp::FlowerPower
+            | PowerUp::MirrorWorld => Allowance::Charges(1),
+            // The rest of the current shot
+            PowerUp::Fireball => Allowance::Shots(1),
+            PowerUp::SuperGuide | PowerUp::Pyramid | PowerUp::Flippers => Allowance::Shots(3),
+        }
+    }
+
+    // So the boxes in the strip can be told apart
+    fn color(self) -> Color {
+        match self {
+            PowerUp::SuperGuide => Color::RGB(80, 200, 255),
+            PowerUp::MultiBall => Color::RGB(255, 255, 255),
+            PowerUp::Pyramid => Color::RGB(230, 190, 60),
+            PowerUp::Explosion => Color::RGB(255, 90, 30),
+            PowerUp::SpookyBall => Color::RGB(150, 90, 200),
+            PowerUp::MagicWheel => Color::RGB(255, 120, 200),
+            PowerUp::Flippers => Color::RGB(200, 120, 0),
+            PowerUp::Fireball => Color::RGB(220, 30, 30),
+            PowerUp::FlowerPower => Color::RGB(60, 200, 80),
+            PowerUp::Zen => Color::RGB(170, 230, 210),
+            PowerUp::MirrorWorld => Color::RGB(140, 140, 160),
+        }
+    }
+}
+
+// Powerups that have been granted and not yet used up, in the order they were granted
+pub struct ActivePowerups {
+    active: Vec<(PowerUp, Allowance)>,
+}
+
+impl ActivePowerups {
+    pub fn new() -> Self {
+        Self { active: Vec::new() }
+    }
+
+    pub fn grant(&mut self, kind: PowerUp) {
+        let granted = kind.allowance();
+        match self.active.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, allowance)) => {
+                *allowance = match (*allowance, granted) {
+                    (Allowance::Charges(have), Allowance::Charges(more)) => {
+                        Allowance::Charges(have + more)
+                    }
+                    (_, refreshed) => refreshed,
+                }
+            }
+            None => self.active.push((kind, granted)),
+        }
+    }
+
+    // Uses up a charge, if there is one. Powerups that last for a number of shots
+    // aren't used up by this, it just tells whether they're active.
+    pub fn consume(&mut self, kind: PowerUp) -> bool {
+        let Some(i) = self.active.iter().position(|(k, _)| *k == kind) else {
+            return false;
+        };
+        if let Allowance::Charges(charges) = &mut self.active[i].1 {
+            *charges -= 1;
+            if *charges == 0 {
+                self.active.remove(i);
+            }
+        }
+        true
+    }
+
+    pub fn is_active(&self, kind: PowerUp) -> bool {
+        self.active.iter().any(|(k, _)| *k == kind)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (PowerUp, Allowance)> + '_ {
+        self.active.iter().copied()
+    }
+
+    // Called whenever a shot is over
+    pub fn tick_shot_boundary(&mut self) {
+        self.active.retain_mut(|(_, allowance)| match allowance {
+            Allowance::Shots(shots) => {
+                *shots -= 1;
+                *shots > 0
+            }
+            Allowance::Charges(_) => true,
+        });
+    }
+}
+
+// A strip in the top left corner with a box per active powerup, in its own color, and a
+// pip under it for every charge or shot it has left
+impl Render for ActivePowerups {
+    fn render<T>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>
+    where
+        T: sdl2::render::RenderTarget,
+    {
+        const SIZE: u32 = 14;
+        const SPACING: i32 = 22;
+        const MAX_PIPS: u32 = 5;
+
+        for (i, (kind, allowance)) in self.iter().enumerate() {
+            let x = 10 + i as i32 * SPACING;
+            let icon = Rect::new(x, 10, SIZE, SIZE);
+            canvas.set_draw_color(kind.color());
+            canvas.fill_rect(icon)?;
+            canvas.set_draw_color(Color::BLACK);
+            canvas.draw_rect(icon)?;
+
+            let (Allowance::Charges(left) | Allowance::Shots(left)) = allowance;
+            canvas.set_draw_color(Color::WHITE);
+            for pip in 0..left.min(MAX_PIPS) {
+                canvas.fill_rect(Rect::new(x + pip as i32 * 3, 10 + SIZE as i32 + 3, 2, 2))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        poggle::PowerUp,
+        powerup::{ActivePowerups, Allowance},
+    };
+
+    #[test]
+    fn test_charges() {
+        let mut powerups = ActivePowerups::new();
+        assert!(!powerups.consume(PowerUp::MultiBall));
+
+        powerups.grant(PowerUp::MultiBall);
+        powerups.grant(PowerUp::MultiBall);
+        assert_eq!(
+            powerups.iter().collect::<Vec<_>>(),
+            vec![(PowerUp::MultiBall, Allowance::Charges(2))]
+        );
+
+        assert!(powerups.consume(PowerUp::MultiBall));
+        assert!(powerups.is_active(PowerUp::MultiBall));
+        assert!(powerups.consume(PowerUp::MultiBall));
+        assert!(!powerups.is_active(PowerUp::MultiBall));
+        assert!(!powerups.consume(PowerUp::MultiBall));
+    }
+
+    #[test]
+    fn test_charges_survive_shots() {
+        let mut powerups = ActivePowerups::new();
+        powerups.grant(PowerUp::Zen);
+        for _ in 0..10 {
+            powerups.tick_shot_boundary();
+        }
+        assert!(powerups.is_active(PowerUp::Zen));
+    }
+
+    #[test]
+    fn test_shot_expiry() {
+        let mut powerups = ActivePowerups::new();
+        powerups.grant(PowerUp::Fireball);
+        powerups.grant(PowerUp::SuperGuide);
+
+        // Using a duration based powerup doesn't use it up
+        assert!(powerups.consume(PowerUp::Fireball));
+        assert!(powerups.is_active(PowerUp::Fireball));
+
+        powerups.tick_shot_boundary();
+        assert_eq!(
+            powerups.iter().collect::<Vec<_>>(),
+            vec![(PowerUp::SuperGuide, Allowance::Shots(2))]
+        );
+        powerups.tick_shot_boundary();
+        assert!(powerups.is_active(PowerUp::SuperGuide));
+        powerups.tick_shot_boundary();
+        assert_eq!(powerups.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_duration_refreshes() {
+        let mut powerups = ActivePowerups::new();
+        powerups.grant(PowerUp::SuperGuide);
+        powerups.tick_shot_boundary();
+        powerups.tick_shot_boundary();
+
+        powerups.grant(PowerUp::SuperGuide);
+        assert_eq!(
+            powerups.iter().collect::<Vec<_>>(),
+            vec![(PowerUp::SuperGuide, Allowance::Shots(3))]
+        );
+    }
+}
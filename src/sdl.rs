@@ -4,21 +4,35 @@ use std::{
 };
 
 use sdl2::{
-    event::Event,
-    keyboard::Keycode,
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Scancode},
     mouse::MouseButton,
     pixels::Color,
     render::{Canvas, RenderTarget},
+    video::Window,
 };
 
-use crate::{poggle::Poggle, shape::Point};
+use crate::{
+    governor::{LoadGovernor, Mitigation},
+    input::FlickTracker,
+    poggle::Poggle,
+    shape::Point,
+};
 
 pub const WINDOW_WIDTH: u32 = 1280;
 pub const WINDOW_HEIGHT: u32 = 800;
 
+// Where flicked shots are fired from
+const LAUNCHER: Point<f32> = Point::new(WINDOW_WIDTH as f32 / 2.0, 50.0);
+
 pub const UPDATES_PER_SECOND: u16 = 165;
 const FRAMES_PER_SECOND: u16 = 165;
 
+// Once a shot is decided it is played out this many times faster. The speedup is done
+// by running extra ticks rather than by changing the timestep, so the simulation
+// itself is identical to running it at normal speed.
+const DECIDED_SHOT_SPEEDUP: u32 = 4;
+
 pub trait Render {
     fn render<T>(&self, canvas: &mut Canvas<T>) -> Result<(), String>
     where
@@ -50,12 +64,14 @@ pub fn run(poggle: &mut Poggle) {
     let window = video
         .window("poggle", WINDOW_WIDTH, WINDOW_HEIGHT)
         .position_centered()
+        .allow_highdpi()
         .build()
         .unwrap();
 
     let mut events = sdl_ctx.event_pump().unwrap();
 
     let mut canvas = window.into_canvas().build().unwrap();
+    let (mut window_size, mut drawable_size) = fit_to_display(&mut canvas);
     canvas.set_draw_color(Color::RED);
     canvas.clear();
     canvas.present();
@@ -65,6 +81,11 @@ pub fn run(poggle: &mut Poggle) {
 
     let mut next_render = Instant::now();
     let render_delta = Duration::from_secs(1) / FRAMES_PER_SECOND as u32;
+
+    let mut governor = LoadGovernor::new(update_delta);
+    let mut spawning_capped = false;
+    // Doubles as the HUD for how many balls have been caught
+    let mut title = String::from("poggle");
     let mut target_start = None;
     let mut target_end = None;
 
@@ -72,6 +93,8 @@ pub fn run(poggle: &mut Poggle) {
     let mut is_suspended = false;
     let mut should_step = false;
     let mut mouse_down = false;
+    let mut flick_mode = false;
+    let mut flick = FlickTracker::new();
 
     while is_running {
         for event in events.poll_iter() {
@@ -81,6 +104,10 @@ pub fn run(poggle: &mut Poggle) {
                     keycode: Some(Keycode::ESCAPE),
                     ..
                 } => is_running = false,
+                Event::Window {
+                    win_event: WindowEvent::DisplayChanged(_) | WindowEvent::SizeChanged(..),
+                    ..
+                } => (window_size, drawable_size) = fit_to_display(&mut canvas),
                 Event::KeyDown {
                     keycode: Some(Keycode::P),
                     ..
@@ -97,28 +124,88 @@ pub fn run(poggle: &mut Poggle) {
                     keycode: Some(Keycode::R),
                     ..
                 } => {
-                    *poggle = Poggle::new();
+                    let (coloring, collisions) = (poggle.ball_coloring(), poggle.ball_collisions());
+                    let show_zones = poggle.show_zones();
+                    *poggle = Poggle::new(Some(*poggle.physics()));
+                    poggle.set_ball_coloring(coloring);
+                    poggle.set_ball_collisions(collisions);
+                    poggle.set_show_zones(show_zones);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::B),
+                    ..
+                } => poggle.set_ball_collisions(!poggle.ball_collisions()),
+                Event::KeyDown {
+                    keycode: Some(Keycode::C),
+                    ..
+                } => poggle.set_ball_coloring(poggle.ball_coloring().next()),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Z),
+                    ..
+                } => poggle.set_show_zones(!poggle.show_zones()),
+                Event::KeyDown {
+                    keycode: Some(Keycode::H),
+                    ..
+                } => eprintln!(
+                    "tick {}: state hash {:#018x}",
+                    poggle.tick(),
+                    poggle.state_hash()
+                ),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F),
+                    ..
+                } => {
+                    flick_mode = !flick_mode;
+                    mouse_down = false;
+                    (target_start, target_end) = (None, None);
                 }
                 Event::MouseButtonDown {
                     mouse_btn: MouseButton::Left,
+                    timestamp,
                     x,
                     y,
                     ..
                 } => {
                     mouse_down = true;
-                    target_start = Some(Point::new(x as f32, y as f32));
-                    target_end = Some(Point::new(x as f32, y as f32));
+                    let p =
+                        normalize_mouse(Point::new(x as f32, y as f32), window_size, drawable_size);
+                    if flick_mode {
+                        flick.begin(timestamp, p);
+                    } else {
+                        target_start = Some(p);
+                        target_end = Some(p);
+                    }
                 }
-                Event::MouseMotion { x, y, .. } => {
-                    let p = Point::new(x as f32, y as f32);
+                Event::MouseMotion {
+                    timestamp, x, y, ..
+                } => {
+                    let p =
+                        normalize_mouse(Point::new(x as f32, y as f32), window_size, drawable_size);
                     if mouse_down {
-                        target_end = Some(p);
+                        if flick_mode {
+                            flick.motion(timestamp, p);
+                        } else {
+                            target_end = Some(p);
+                        }
                     }
                 }
                 Event::MouseButtonUp {
                     mouse_btn: MouseButton::Left,
+                    timestamp,
+                    x,
+                    y,
                     ..
                 } => {
+                    if flick_mode && mouse_down && !spawning_capped && !poggle.is_mirroring() {
+                        let p = normalize_mouse(
+                            Point::new(x as f32, y as f32),
+                            window_size,
+                            drawable_size,
+                        );
+                        if let Some(velocity) = flick.release(timestamp, p) {
+                            poggle.shoot(LAUNCHER, velocity);
+                        }
+                    }
                     mouse_down = false;
                     (target_start, target_end) = (None, None);
                 }
@@ -130,17 +217,26 @@ pub fn run(poggle: &mut Poggle) {
                     mouse_btn: MouseButton::Right,
                     ..
                 } => {
-                    if let (Some(start), Some(end)) = (target_start, target_end) {
-                        if mouse_down {
-                            let velocity = start.to(end);
-                            poggle.shoot(start, velocity);
-                        }
+                    if let (Some(start), Some(end)) = (target_start, target_end)
+                        && mouse_down
+                        && !spawning_capped
+                        && !poggle.is_mirroring()
+                    {
+                        let velocity = start.to(end);
+                        poggle.shoot(start, velocity);
                     }
                 }
                 _ => {}
             }
         }
 
+        // Held rather than pressed, so this goes by the keyboard state
+        let keys = events.keyboard_state();
+        poggle.set_flipper_input(
+            keys.is_scancode_pressed(Scancode::Left),
+            keys.is_scancode_pressed(Scancode::Right),
+        );
+
         if is_suspended && !should_step {
             thread::sleep(Duration::from_micros(10));
             continue;
@@ -154,18 +250,60 @@ pub fn run(poggle: &mut Poggle) {
             next_render = (next_render + render_delta).max(now);
             canvas.set_draw_color(Color::GRAY);
             canvas.clear();
-            poggle.render(&mut canvas).expect("rendering driver failed");
+            let mut drawn = poggle.render(&mut canvas);
             if let (Some(start), Some(end)) = (target_start, target_end) {
                 canvas.set_draw_color(Color::RED);
-                canvas
-                    .draw_line(start, end)
-                    .expect("rendering driver failed");
+                drawn = drawn.and(canvas.draw_line(start, end));
+            }
+            if let Err(err) = drawn {
+                eprintln!("error: rendering failed, quitting: {err}");
+                break;
             }
             canvas.present();
         }
 
         if now >= next_update {
-            poggle.update(update_delta);
+            let ticks = if poggle.shot_is_decided() {
+                DECIDED_SHOT_SPEEDUP
+            } else {
+                1
+            };
+            let started = Instant::now();
+            for _ in 0..ticks {
+                poggle.update(update_delta);
+            }
+            for mitigation in governor.observe(started.elapsed() / ticks) {
+                match mitigation {
+                    Mitigation::CapSpawning => {
+                        eprintln!("warning: updates are over budget, no longer spawning balls");
+                        spawning_capped = true;
+                    }
+                    Mitigation::UncapSpawning => {
+                        eprintln!("load recovered, spawning balls again");
+                        spawning_capped = false;
+                    }
+                    Mitigation::CullBalls => {
+                        poggle.cull_oldest_balls(0.25);
+                        eprintln!(
+                            "warning: updates are still over budget, removed oldest balls ({} left)",
+                            poggle.ball_count()
+                        );
+                    }
+                }
+            }
+            let mode = if governor.is_mitigating() {
+                " (performance mode)"
+            } else {
+                ""
+            };
+            let wanted = format!("poggle{mode} - {} caught", poggle.caught());
+            if wanted != title {
+                canvas
+                    .window_mut()
+                    .set_title(&wanted)
+                    .expect("window title has no nul bytes");
+                title = wanted;
+            }
             next_update = (next_update + update_delta).max(now);
         }
 
@@ -173,6 +311,67 @@ pub fn run(poggle: &mut Poggle) {
     }
 }
 
+// How many drawable pixels there are per window coordinate, 1.0 on regular displays
+fn pixel_density(window_size: (u32, u32), drawable_size: (u32, u32)) -> f32 {
+    if window_size.0 == 0 {
+        return 1.0;
+    }
+    drawable_size.0 as f32 / window_size.0 as f32
+}
+
+// Everything is drawn in board coordinates, scaled up to however many pixels the window
+// really has, which differs from the window size on HiDPI displays. Returns the window
+// and drawable sizes for mapping mouse input. Called again whenever the window moves to
+// another display.
+fn fit_to_display(canvas: &mut Canvas<Window>) -> ((u32, u32), (u32, u32)) {
+    let window_size = canvas.window().size();
+    let drawable_size = canvas.output_size().unwrap_or(window_size);
+    canvas
+        .set_scale(
+            drawable_size.0 as f32 / WINDOW_WIDTH as f32,
+            drawable_size.1 as f32 / WINDOW_HEIGHT as f32,
+        )
+        .expect("drawable size gives a valid scale");
+    (window_size, drawable_size)
+}
+
+// SDL gives mouse positions in window coordinates, which are points on some platforms
+// and pixels on others, and only maps them onto the board itself when the canvas has a
+// logical size. This takes them to pixels and from there onto the board.
+fn normalize_mouse(
+    pos: Point<f32>,
+    window_size: (u32, u32),
+    drawable_size: (u32, u32),
+) -> Point<f32> {
+    if drawable_size.0 == 0 || drawable_size.1 == 0 {
+        return pos;
+    }
+    let pixels = pos * pixel_density(window_size, drawable_size);
+    Point::new(
+        pixels.x * WINDOW_WIDTH as f32 / drawable_size.0 as f32,
+        pixels.y * WINDOW_HEIGHT as f32 / drawable_size.1 as f32,
+    )
+}
+
+// Shapes are rasterized in real pixels rather than in board coordinates that get scaled
+// up afterwards, so they stay crisp on HiDPI displays. `draw` gets the scale to apply to
+// its own coordinates.
+fn at_native_density<T, F>(canvas: &mut Canvas<T>, draw: F) -> Result<(), String>
+where
+    T: RenderTarget,
+    F: FnOnce(&mut Canvas<T>, Point<f32>) -> Result<(), String>,
+{
+    let (x, y) = canvas.scale();
+    canvas.set_scale(1.0, 1.0)?;
+    let drawn = draw(canvas, Point::new(x, y));
+    canvas.set_scale(x, y)?;
+    drawn
+}
+
+fn to_pixels(point: Point<f32>, scale: Point<f32>) -> Point<f32> {
+    Point::new(point.x * scale.x, point.y * scale.y)
+}
+
 fn get_octant_offsets(radius: u32) -> Vec<Point<i32>> {
     let mut offsets = Vec::with_capacity((radius as usize + 1) * 2);
     let (mut dx, mut dy) = (0, radius as i32);
@@ -202,30 +401,131 @@ pub fn draw_circle_filled<T>(
 where
     T: RenderTarget,
 {
-    let center = Point::new(x, y);
-    for offset in get_octant_offsets(radius) {
-        let (dx, dy) = (offset.x, offset.y);
-        for d in [
-            Point::new(dx, dy),
-            Point::new(dy, dx),
-            Point::new(dy, -dx),
-            Point::new(dx, -dy),
-        ] {
-            let other = Point::new(-d.x, d.y);
-            canvas.draw_line(center.add_signed(other), center.add_signed(d))?;
+    at_native_density(canvas, |canvas, scale| {
+        let (center, radius) = circle_in_pixels(x, y, radius, scale);
+        for offset in get_octant_offsets(radius) {
+            let (dx, dy) = (offset.x, offset.y);
+            for d in [
+                Point::new(dx, dy),
+                Point::new(dy, dx),
+                Point::new(dy, -dx),
+                Point::new(dx, -dy),
+            ] {
+                let other = Point::new(-d.x, d.y);
+                canvas.draw_line(center.add_signed(other), center.add_signed(d))?;
+            }
+        }
+        Ok(())
+    })
+}
+
+fn circle_in_pixels(x: u32, y: u32, radius: u32, scale: Point<f32>) -> (Point<u32>, u32) {
+    let center = to_pixels(Point::new(x as f32, y as f32), scale);
+    (
+        Point::new(center.x.round() as u32, center.y.round() as u32),
+        (radius as f32 * scale.x).round() as u32,
+    )
+}
+
+fn polygon_vertices(points: &[Point<f32>], center: Point<f32>, rotation: f32) -> Vec<Point<f32>> {
+    points
+        .iter()
+        .map(|p| center + p.rotated(rotation))
+        .collect()
+}
+
+// Fills using scanlines with the even-odd rule, so concave shapes are filled correctly
+pub fn draw_polygon_filled<T>(
+    canvas: &mut Canvas<T>,
+    points: &[Point<f32>],
+    center: Point<f32>,
+    rotation: f32,
+) -> Result<(), String>
+where
+    T: RenderTarget,
+{
+    at_native_density(canvas, |canvas, scale| {
+        let vertices: Vec<_> = polygon_vertices(points, center, rotation)
+            .into_iter()
+            .map(|p| to_pixels(p, scale))
+            .collect();
+        fill_polygon(canvas, &vertices)
+    })
+}
+
+fn fill_polygon<T>(canvas: &mut Canvas<T>, vertices: &[Point<f32>]) -> Result<(), String>
+where
+    T: RenderTarget,
+{
+    let edges: Vec<_> = vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .collect();
+
+    let top = vertices.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+    let bottom = vertices
+        .iter()
+        .map(|p| p.y)
+        .fold(f32::NEG_INFINITY, f32::max);
+    if !top.is_finite() || !bottom.is_finite() {
+        return Ok(());
+    }
+
+    let mut crossings = Vec::new();
+    for y in top.floor() as i32..=bottom.ceil() as i32 {
+        // Sample through the middle of the pixel row
+        let scan = y as f32 + 0.5;
+        crossings.clear();
+        for (a, b) in &edges {
+            if (a.y <= scan) != (b.y <= scan) {
+                crossings.push(a.x + (scan - a.y) / (b.y - a.y) * (b.x - a.x));
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+
+        for span in crossings.chunks_exact(2) {
+            canvas.draw_line(
+                sdl2::rect::Point::new(span[0].round() as i32, y),
+                sdl2::rect::Point::new(span[1].round() as i32, y),
+            )?;
         }
     }
     Ok(())
 }
 
-pub fn draw_circle<T>(canvas: &mut Canvas<T>, x: u32, y: u32, radius: u32) -> Result<(), String>
+pub fn draw_polygon<T>(
+    canvas: &mut Canvas<T>,
+    points: &[Point<f32>],
+    center: Point<f32>,
+    rotation: f32,
+) -> Result<(), String>
 where
     T: RenderTarget,
 {
-    let center = Point::new(x, y);
-    for offset in get_octant_offsets(radius) {
+    at_native_density(canvas, |canvas, scale| {
+        let vertices = polygon_vertices(points, center, rotation);
+        for (a, b) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+            canvas.draw_line(to_pixels(*a, scale), to_pixels(*b, scale))?;
+        }
+        Ok(())
+    })
+}
+
+pub fn centered_rect(center: Point<f32>, half_extents: Point<f32>) -> sdl2::rect::Rect {
+    let corner = center - half_extents;
+    sdl2::rect::Rect::new(
+        corner.x.round() as i32,
+        corner.y.round() as i32,
+        (half_extents.x * 2.0).round() as u32,
+        (half_extents.y * 2.0).round() as u32,
+    )
+}
+
+// Every point on the outline of a circle around the origin
+fn circle_offsets(radius: u32) -> impl Iterator<Item = Point<i32>> {
+    get_octant_offsets(radius).into_iter().flat_map(|offset| {
         let (dx, dy) = (offset.x, offset.y);
-        for d in [
+        [
             Point::new(dx, dy),
             Point::new(dx, -dy),
             Point::new(-dx, dy),
@@ -234,9 +534,118 @@ where
             Point::new(dy, -dx),
             Point::new(-dy, dx),
             Point::new(-dy, -dx),
-        ] {
+        ]
+    })
+}
+
+pub fn draw_circle<T>(canvas: &mut Canvas<T>, x: u32, y: u32, radius: u32) -> Result<(), String>
+where
+    T: RenderTarget,
+{
+    at_native_density(canvas, |canvas, scale| {
+        let (center, radius) = circle_in_pixels(x, y, radius, scale);
+        for d in circle_offsets(radius) {
             canvas.draw_point(center.add_signed(d))?;
         }
+        Ok(())
+    })
+}
+
+// Two circles joined by a quad between their sides
+pub fn draw_capsule_filled<T>(
+    canvas: &mut Canvas<T>,
+    a: Point<f32>,
+    b: Point<f32>,
+    radius: f32,
+) -> Result<(), String>
+where
+    T: RenderTarget,
+{
+    for end in [a, b] {
+        draw_circle_filled(canvas, end.x as u32, end.y as u32, radius as u32)?;
+    }
+    if a == b {
+        return Ok(());
+    }
+    let side = capsule_side(a, b, radius);
+    draw_polygon_filled(
+        canvas,
+        &[a + side, b + side, b - side, a - side],
+        Point::zero(),
+        0.0,
+    )
+}
+
+// Only the outer half of each end is drawn, so no arcs show up inside the capsule
+pub fn draw_capsule<T>(
+    canvas: &mut Canvas<T>,
+    a: Point<f32>,
+    b: Point<f32>,
+    radius: f32,
+) -> Result<(), String>
+where
+    T: RenderTarget,
+{
+    if a == b {
+        return draw_circle(canvas, a.x as u32, a.y as u32, radius as u32);
+    }
+    let side = capsule_side(a, b, radius);
+    let along = a.to(b);
+    at_native_density(canvas, |canvas, scale| {
+        for side in [side, -side] {
+            canvas.draw_line(to_pixels(a + side, scale), to_pixels(b + side, scale))?;
+        }
+        for (end, outward) in [(a, -along), (b, along)] {
+            let (center, radius) =
+                circle_in_pixels(end.x as u32, end.y as u32, radius as u32, scale);
+            for d in circle_offsets(radius) {
+                if d.x as f32 * outward.x + d.y as f32 * outward.y >= 0.0 {
+                    canvas.draw_point(center.add_signed(d))?;
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+// From the segment out to one side of a capsule
+fn capsule_side(a: Point<f32>, b: Point<f32>, radius: f32) -> Point<f32> {
+    let along = a.to(b);
+    Point::new(-along.y, along.x).with_length(radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        sdl::{WINDOW_HEIGHT, WINDOW_WIDTH, normalize_mouse, pixel_density},
+        shape::Point,
+    };
+
+    #[test]
+    fn test_pixel_density() {
+        assert_eq!(pixel_density((1280, 800), (1280, 800)), 1.0);
+        assert_eq!(pixel_density((1280, 800), (1920, 1200)), 1.5);
+        assert_eq!(pixel_density((1280, 800), (2560, 1600)), 2.0);
+        assert_eq!(pixel_density((0, 0), (0, 0)), 1.0);
+    }
+
+    #[test]
+    fn test_normalize_mouse() {
+        let board = (WINDOW_WIDTH, WINDOW_HEIGHT);
+        let scaled = |factor: f32| {
+            (
+                (WINDOW_WIDTH as f32 * factor) as u32,
+                (WINDOW_HEIGHT as f32 * factor) as u32,
+            )
+        };
+        let pos = Point::new(320.0, 200.0);
+        for factor in [1.0, 1.5, 2.0] {
+            // Points, so the window is the size of the board whatever the density
+            assert_eq!(normalize_mouse(pos, board, scaled(factor)), pos);
+            // Pixels, where the window is as big as what gets drawn
+            let pixels = pos * factor;
+            assert_eq!(normalize_mouse(pixels, scaled(factor), scaled(factor)), pos);
+        }
+        assert_eq!(normalize_mouse(pos, (0, 0), (0, 0)), pos);
     }
-    Ok(())
 }
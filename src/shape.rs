@@ -1,6 +1,6 @@
 use std::{
     fmt::Display,
-    ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub},
+    ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
 pub trait Number:
@@ -91,6 +91,13 @@ impl<T: Number + Into<f32>> Point<T> {
     }
 }
 
+impl Point<f32> {
+    pub fn rotated(self, angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+}
+
 impl PolarPoint {
     pub const fn new(angle: f32, magnitude: f32) -> Self {
         Self { angle, magnitude }
@@ -104,10 +111,6 @@ impl<T: Number + From<u8>> Point<T> {
             y: 0u8.into(),
         }
     }
-
-    pub fn kinetic_energy(self) -> T {
-        self.length_squared() / 2u8.into()
-    }
 }
 
 impl<T: Number> Add for Point<T> {
@@ -133,6 +136,13 @@ impl<T: Number> Sub for Point<T> {
     }
 }
 
+impl<T: Number + SubAssign> SubAssign for Point<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+    }
+}
+
 impl<T: Number> Mul<T> for Point<T> {
     type Output = Self;
 
@@ -191,16 +201,32 @@ impl Point<u32> {
     }
 }
 
+#[derive(Clone)]
 pub enum Shape {
     Circle {
         radius: f32,
     },
+    // A polygon with `rounding` > 0 also covers everything within that distance of its
+    // outline, so the corners become arcs
     Polygon {
         points: Vec<Point<f32>>,
         rotation: f32,
+        rounding: f32,
+    },
+    // Axis aligned, so it never turns. Rounded like a polygon.
+    Rect {
+        half_extents: Point<f32>,
+        rounding: f32,
+    },
+    // Everything within `radius` of the segment from `a` to `b`
+    Capsule {
+        a: Point<f32>,
+        b: Point<f32>,
+        radius: f32,
     },
 }
 
+#[derive(Clone)]
 pub struct Body {
     pub pos: Point<f32>,
     pub shape: Shape,
@@ -212,13 +238,222 @@ impl Body {
             Shape::Circle { radius } => Shape::Circle {
                 radius: radius + distance,
             },
-            Shape::Polygon { points, rotation } => todo!(),
+            Shape::Polygon {
+                points,
+                rotation,
+                rounding,
+            } => Shape::Polygon {
+                points: points.clone(),
+                rotation: *rotation,
+                rounding: rounding + distance,
+            },
+            Shape::Rect {
+                half_extents,
+                rounding,
+            } => Shape::Rect {
+                half_extents: *half_extents,
+                rounding: rounding + distance,
+            },
+            Shape::Capsule { a, b, radius } => Shape::Capsule {
+                a: *a,
+                b: *b,
+                radius: radius + distance,
+            },
         };
         Self {
             pos: self.pos,
             shape,
         }
     }
+
+    // Flips the body around the vertical line at x = `axis`
+    pub fn mirror(&mut self, axis: f32) {
+        self.pos.x = 2.0 * axis - self.pos.x;
+        match &mut self.shape {
+            Shape::Circle { .. } | Shape::Rect { .. } => {}
+            Shape::Polygon {
+                points, rotation, ..
+            } => {
+                for point in points {
+                    point.x = -point.x;
+                }
+                *rotation = -*rotation;
+            }
+            Shape::Capsule { a, b, .. } => {
+                a.x = -a.x;
+                b.x = -b.x;
+            }
+        }
+    }
+
+    // Radius of the smallest circle around `pos` that contains the whole body
+    pub fn bounding_radius(&self) -> f32 {
+        match &self.shape {
+            Shape::Circle { radius } => *radius,
+            Shape::Polygon {
+                points, rounding, ..
+            } => points.iter().map(|p| p.length()).fold(0.0, f32::max) + rounding,
+            Shape::Rect {
+                half_extents,
+                rounding,
+            } => half_extents.length() + rounding,
+            Shape::Capsule { a, b, radius } => a.length().max(b.length()) + radius,
+        }
+    }
+
+    // The largest y coordinate covered by the body, i.e. its lowest point on screen
+    pub fn bottom(&self) -> f32 {
+        match &self.shape {
+            Shape::Circle { radius } => self.pos.y + radius,
+            Shape::Polygon {
+                points,
+                rotation,
+                rounding,
+            } => {
+                points
+                    .iter()
+                    .map(|p| self.pos.y + p.rotated(*rotation).y)
+                    .fold(f32::NEG_INFINITY, f32::max)
+                    + rounding
+            }
+            Shape::Rect {
+                half_extents,
+                rounding,
+            } => self.pos.y + half_extents.y + rounding,
+            Shape::Capsule { a, b, radius } => self.pos.y + a.y.max(b.y) + radius,
+        }
+    }
+
+    // Corners of a polygon or rectangle, or the ends of a capsule, in world space. Empty
+    // for circles. Rounding is ignored.
+    pub fn vertices(&self) -> Vec<Point<f32>> {
+        match &self.shape {
+            Shape::Circle { .. } => Vec::new(),
+            Shape::Rect { half_extents, .. } => {
+                let Point { x, y } = *half_extents;
+                [(-x, -y), (x, -y), (x, y), (-x, y)]
+                    .into_iter()
+                    .map(|(x, y)| self.pos + Point::new(x, y))
+                    .collect()
+            }
+            Shape::Capsule { a, b, .. } => vec![self.pos + *a, self.pos + *b],
+            Shape::Polygon {
+                points, rotation, ..
+            } => points
+                .iter()
+                .map(|p| self.pos + p.rotated(*rotation))
+                .collect(),
+        }
+    }
+
+    // Edges of a polygon in world space as (start, end, outward normal). A capsule's
+    // segment is an edge both ways round, one for each side.
+    pub fn edges(&self) -> Vec<(Point<f32>, Point<f32>, Point<f32>)> {
+        let vertices = self.vertices();
+        if vertices.len() < 2 {
+            return Vec::new();
+        }
+
+        // The sign of the area tells which way the points wind, and so which side of
+        // each edge is the outside
+        let pairs = || vertices.iter().zip(vertices.iter().cycle().skip(1));
+        let area: f32 = pairs().map(|(a, b)| a.x * b.y - b.x * a.y).sum();
+        let winding = if area < 0.0 { -1.0 } else { 1.0 };
+
+        pairs()
+            .map(|(a, b)| {
+                let edge = a.to(*b);
+                (*a, *b, Point::new(edge.y, -edge.x).normalized() * winding)
+            })
+            .collect()
+    }
+
+    // The point on the outline of the body closest to `p`
+    pub fn closest_surface_point(&self, p: Point<f32>) -> Point<f32> {
+        match &self.shape {
+            Shape::Circle { radius } => {
+                if p == self.pos {
+                    return self.pos + Point::new(*radius, 0.0);
+                }
+                self.pos + self.pos.to(p).with_length(*radius)
+            }
+            Shape::Polygon { rounding, .. }
+            | Shape::Rect { rounding, .. }
+            | Shape::Capsule {
+                radius: rounding, ..
+            } => {
+                let outline = self.closest_outline_point(p);
+                if *rounding > 0.0 {
+                    outline + self.normal_at(p) * *rounding
+                } else {
+                    outline
+                }
+            }
+        }
+    }
+
+    // Closest point on the polygon's edges, before any rounding
+    fn closest_outline_point(&self, p: Point<f32>) -> Point<f32> {
+        self.edges()
+            .into_iter()
+            .map(|(a, b, _)| closest_point_on_segment(p, a, b))
+            .min_by(|q1, q2| {
+                p.distance_to_squared(*q1)
+                    .total_cmp(&p.distance_to_squared(*q2))
+            })
+            .unwrap_or(self.pos)
+    }
+
+    // Outward surface normal at the part of the outline closest to `p`. Near a polygon
+    // corner this points from the corner to `p`, so rounded-off corners bounce correctly.
+    pub fn normal_at(&self, p: Point<f32>) -> Point<f32> {
+        match &self.shape {
+            Shape::Circle { .. } => self.pos.to(p).normalized(),
+            Shape::Rect { half_extents, .. } => rect_normal(self.pos.to(p), *half_extents),
+            Shape::Capsule { a, b, .. } => {
+                let (a, b) = (self.pos + *a, self.pos + *b);
+                let offset = closest_point_on_segment(p, a, b).to(p);
+                if offset.length_squared() > f32::EPSILON {
+                    offset.normalized()
+                } else if a != b {
+                    // Right on the segment, so either side will do
+                    let along = a.to(b);
+                    Point::new(along.y, -along.x).normalized()
+                } else {
+                    Point::new(0.0, -1.0)
+                }
+            }
+            Shape::Polygon { .. } => {
+                let Some((a, b, normal)) =
+                    self.edges().into_iter().min_by(|(a1, b1, _), (a2, b2, _)| {
+                        p.distance_to_squared(closest_point_on_segment(p, *a1, *b1))
+                            .total_cmp(
+                                &p.distance_to_squared(closest_point_on_segment(p, *a2, *b2)),
+                            )
+                    })
+                else {
+                    return Point::zero();
+                };
+
+                let offset = closest_point_on_segment(p, a, b).to(p);
+                if normal.dot(a.to(p)) > 0.0 && offset.length_squared() > f32::EPSILON {
+                    offset.normalized()
+                } else {
+                    normal
+                }
+            }
+        }
+    }
+}
+
+pub fn closest_point_on_segment(p: Point<f32>, a: Point<f32>, b: Point<f32>) -> Point<f32> {
+    let ab = a.to(b);
+    let length_squared = ab.length_squared();
+    if length_squared == 0.0 {
+        return a;
+    }
+    let t = (a.to(p).dot(ab) / length_squared).clamp(0.0, 1.0);
+    a + ab * t
 }
 
 pub trait Region {
@@ -229,9 +464,80 @@ impl Region for Body {
     fn contains(&self, p: Point<f32>) -> bool {
         match &self.shape {
             Shape::Circle { radius } => (self.pos - p).length_squared() <= *radius * *radius,
-            Shape::Polygon { points, rotation } => todo!(),
+            Shape::Polygon { rounding, .. } => {
+                polygon_contains(&self.vertices(), p)
+                    || (*rounding > 0.0
+                        && p.distance_to_squared(self.closest_outline_point(p))
+                            <= rounding * rounding)
+            }
+            Shape::Rect {
+                half_extents,
+                rounding,
+            } => {
+                let offset = self.pos.to(p);
+                if *rounding == 0.0 {
+                    offset.x.abs() <= half_extents.x && offset.y.abs() <= half_extents.y
+                } else {
+                    offset.distance_to_squared(clamp_to_rect(offset, *half_extents))
+                        <= rounding * rounding
+                }
+            }
+            Shape::Capsule { a, b, radius } => {
+                let closest = closest_point_on_segment(p, self.pos + *a, self.pos + *b);
+                p.distance_to_squared(closest) <= radius * radius
+            }
+        }
+    }
+}
+
+// Winding number test, so concave polygons work too. Points on an edge or corner count
+// as inside, like points on the edge of a circle do.
+fn polygon_contains(vertices: &[Point<f32>], p: Point<f32>) -> bool {
+    const ON_EDGE: f32 = 1e-4;
+
+    let mut winding = 0;
+    for (a, b) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+        if p.distance_to_squared(closest_point_on_segment(p, *a, *b)) <= ON_EDGE * ON_EDGE {
+            return true;
+        }
+
+        // Which side of the edge the point is on
+        let side = a.to(*b).x * a.to(p).y - a.to(*b).y * a.to(p).x;
+        if a.y <= p.y {
+            if b.y > p.y && side > 0.0 {
+                winding += 1;
+            }
+        } else if b.y <= p.y && side < 0.0 {
+            winding -= 1;
         }
     }
+    winding != 0
+}
+
+// Everything about rectangles is worked out relative to their center
+fn clamp_to_rect(offset: Point<f32>, half_extents: Point<f32>) -> Point<f32> {
+    Point::new(
+        offset.x.clamp(-half_extents.x, half_extents.x),
+        offset.y.clamp(-half_extents.y, half_extents.y),
+    )
+}
+
+// Past a corner this points from the corner, so balls bounce off corners at an angle
+// instead of off whichever face is nearest
+fn rect_normal(offset: Point<f32>, half_extents: Point<f32>) -> Point<f32> {
+    let outside = Point::new(
+        offset.x.abs() - half_extents.x,
+        offset.y.abs() - half_extents.y,
+    );
+    if outside.x > 0.0 || outside.y > 0.0 {
+        return clamp_to_rect(offset, half_extents).to(offset).normalized();
+    }
+    // Inside, out through the nearest face
+    if outside.x > outside.y {
+        Point::new(offset.x.signum(), 0.0)
+    } else {
+        Point::new(0.0, offset.y.signum())
+    }
 }
 
 pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
@@ -248,9 +554,136 @@ pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
     Some((midpoint + delta, midpoint - delta))
 }
 
+// When a circle moving with `velocity` first touches a resting one within `time`, as the
+// fraction of `time` it takes and where the moving circle's center is by then. Circles
+// that already overlap only count if they're moving further into each other.
+pub fn sweep_circle_circle(
+    start: Point<f32>,
+    velocity: Point<f32>,
+    moving_radius: f32,
+    center: Point<f32>,
+    resting_radius: f32,
+    time: f32,
+) -> Option<(f32, Point<f32>)> {
+    // |offset + movement * t| = r is a quadratic in t
+    let offset = center.to(start);
+    let movement = velocity * time;
+    let r = moving_radius + resting_radius;
+
+    let c = offset.length_squared() - r * r;
+    let b = 2.0 * offset.dot(movement);
+    if c <= 0.0 {
+        return (b < 0.0).then_some((0.0, start));
+    }
+
+    let a = movement.length_squared();
+    if a <= f32::EPSILON {
+        return None;
+    }
+    let (_, first) = solve_quadratic(a, b, c)?;
+    (0.0..=1.0)
+        .contains(&first)
+        .then(|| (first, start + movement * first))
+}
+
+// Like `sweep_circle_circle`, but against a rectangle centered on `center`. The circle's
+// center can't get within `moving_radius + rounding` of the rectangle, so it's stopped
+// either by one of the faces pushed out by that much or by one of the rounded corners.
+pub fn sweep_circle_rect(
+    start: Point<f32>,
+    velocity: Point<f32>,
+    moving_radius: f32,
+    center: Point<f32>,
+    half_extents: Point<f32>,
+    rounding: f32,
+    time: f32,
+) -> Option<(f32, Point<f32>)> {
+    let offset = center.to(start);
+    let movement = velocity * time;
+    let r = moving_radius + rounding;
+    let reach = half_extents + Point::new(r, r);
+
+    // Cheap rejection when the box around the path misses the grown rectangle
+    let end = offset + movement;
+    if offset.x.min(end.x) > reach.x
+        || offset.x.max(end.x) < -reach.x
+        || offset.y.min(end.y) > reach.y
+        || offset.y.max(end.y) < -reach.y
+    {
+        return None;
+    }
+
+    if offset.distance_to_squared(clamp_to_rect(offset, half_extents)) <= r * r {
+        let normal = rect_normal(offset, half_extents);
+        return (normal.dot(movement) < 0.0).then_some((0.0, start));
+    }
+
+    let mut earliest: Option<f32> = None;
+    let mut consider = |t: f32| {
+        if (0.0..=1.0).contains(&t) && earliest.is_none_or(|e| t < e) {
+            earliest = Some(t);
+        }
+    };
+
+    // Only the face on the side the circle comes from can be hit, and only between the
+    // corners
+    let axes = [
+        (
+            offset.x,
+            movement.x,
+            half_extents.x,
+            offset.y,
+            movement.y,
+            half_extents.y,
+        ),
+        (
+            offset.y,
+            movement.y,
+            half_extents.y,
+            offset.x,
+            movement.x,
+            half_extents.x,
+        ),
+    ];
+    for (along, moving, half, across, drift, width) in axes {
+        if moving == 0.0 {
+            continue;
+        }
+        let face = -(half + r) * moving.signum();
+        let t = (face - along) / moving;
+        if (across + drift * t).abs() <= width {
+            consider(t);
+        }
+    }
+
+    for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        let corner = center + Point::new(half_extents.x * x, half_extents.y * y);
+        if let Some((t, _)) =
+            sweep_circle_circle(start, velocity, moving_radius, corner, rounding, time)
+        {
+            consider(t);
+        }
+    }
+
+    earliest.map(|t| (t, start + movement * t))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::shape::Point;
+    use std::f32::consts;
+
+    use crate::shape::{Body, Point, Region, Shape, sweep_circle_circle, sweep_circle_rect};
+
+    fn polygon(pos: Point<f32>, points: &[(f32, f32)], rotation: f32) -> Body {
+        Body {
+            pos,
+            shape: Shape::Polygon {
+                points: points.iter().map(|&(x, y)| Point::new(x, y)).collect(),
+                rotation,
+                rounding: 0.0,
+            },
+        }
+    }
 
     #[test]
     fn test_add() {
@@ -290,4 +723,331 @@ mod tests {
         assert!((b.length() - 5.0f32).abs() < f32::EPSILON);
         assert!((c.length() - 61.0f32.sqrt()).abs() < f32::EPSILON);
     }
+
+    #[test]
+    fn test_contains_square() {
+        let square = polygon(
+            Point::new(100.0, 100.0),
+            &[(-10.0, -10.0), (10.0, -10.0), (10.0, 10.0), (-10.0, 10.0)],
+            0.0,
+        );
+
+        assert!(square.contains(Point::new(100.0, 100.0)));
+        assert!(square.contains(Point::new(109.0, 91.0)));
+        assert!(!square.contains(Point::new(111.0, 100.0)));
+        assert!(!square.contains(Point::new(100.0, 89.0)));
+        assert!(!square.contains(Point::new(0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_contains_triangle() {
+        // Wound the other way around compared to the square
+        let triangle = polygon(Point::zero(), &[(0.0, 0.0), (0.0, 10.0), (10.0, 0.0)], 0.0);
+
+        assert!(triangle.contains(Point::new(2.0, 2.0)));
+        assert!(!triangle.contains(Point::new(6.0, 6.0)));
+        assert!(!triangle.contains(Point::new(-1.0, 2.0)));
+    }
+
+    #[test]
+    fn test_contains_rotated_rectangle() {
+        // 40 wide and 10 tall, stood upright by the rotation
+        let rectangle = polygon(
+            Point::new(50.0, 50.0),
+            &[(-20.0, -5.0), (20.0, -5.0), (20.0, 5.0), (-20.0, 5.0)],
+            consts::FRAC_PI_2,
+        );
+
+        assert!(rectangle.contains(Point::new(50.0, 68.0)));
+        assert!(rectangle.contains(Point::new(53.0, 32.0)));
+        assert!(!rectangle.contains(Point::new(68.0, 50.0)));
+    }
+
+    #[test]
+    fn test_contains_on_edge() {
+        let square = polygon(
+            Point::zero(),
+            &[(-10.0, -10.0), (10.0, -10.0), (10.0, 10.0), (-10.0, 10.0)],
+            0.0,
+        );
+
+        assert!(square.contains(Point::new(10.0, 3.0)));
+        assert!(square.contains(Point::new(-4.0, -10.0)));
+        assert!(square.contains(Point::new(10.0, 10.0)));
+        assert!(!square.contains(Point::new(10.01, 10.0)));
+    }
+
+    #[test]
+    fn test_contains_concave() {
+        // A U shape opening upwards
+        let u = polygon(
+            Point::zero(),
+            &[
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 30.0),
+                (20.0, 30.0),
+                (20.0, 0.0),
+                (30.0, 0.0),
+                (30.0, 40.0),
+                (0.0, 40.0),
+            ],
+            0.0,
+        );
+
+        assert!(u.contains(Point::new(5.0, 10.0)));
+        assert!(u.contains(Point::new(25.0, 10.0)));
+        assert!(u.contains(Point::new(15.0, 35.0)));
+        assert!(!u.contains(Point::new(15.0, 10.0)));
+    }
+
+    #[test]
+    fn test_extended_polygon_contains() {
+        let square = polygon(
+            Point::zero(),
+            &[(-10.0, -10.0), (10.0, -10.0), (10.0, 10.0), (-10.0, 10.0)],
+            0.0,
+        );
+        let extended = square.extend(5.0);
+
+        assert!(extended.contains(Point::zero()));
+        assert!(extended.contains(Point::new(14.9, 0.0)));
+        assert!(extended.contains(Point::new(0.0, -15.0)));
+        assert!(!extended.contains(Point::new(15.1, 0.0)));
+
+        // Beyond the corners the region is an arc, not a square
+        assert!(extended.contains(Point::new(13.0, 13.0)));
+        assert!(!extended.contains(Point::new(14.0, 14.0)));
+        assert!(!extended.contains(Point::new(-14.5, 14.5)));
+
+        assert_eq!(extended.bounding_radius(), 200f32.sqrt() + 5.0);
+        assert_eq!(extended.bottom(), 15.0);
+    }
+
+    #[test]
+    fn test_sweep_head_on() {
+        let center = Point::new(100.0, 100.0);
+        let (toi, point) = sweep_circle_circle(
+            Point::new(100.0, 0.0),
+            Point::new(0.0, 1000.0),
+            6.0,
+            center,
+            6.0,
+            0.1,
+        )
+        .unwrap();
+        assert!((toi - 0.88).abs() < 1e-5);
+        assert!(point.distance_to(Point::new(100.0, 88.0)) < 1e-4);
+
+        // Would get there, just not this soon
+        assert!(
+            sweep_circle_circle(
+                Point::new(100.0, 0.0),
+                Point::new(0.0, 1000.0),
+                6.0,
+                center,
+                6.0,
+                0.05
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_sweep_graze() {
+        // Passes exactly one combined radius from the center, touching it halfway
+        let (toi, point) = sweep_circle_circle(
+            Point::new(-10.0, 12.0),
+            Point::new(20.0, 0.0),
+            6.0,
+            Point::zero(),
+            6.0,
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(toi, 0.5);
+        assert_eq!(point, Point::new(0.0, 12.0));
+
+        assert!(
+            sweep_circle_circle(
+                Point::new(-10.0, 12.1),
+                Point::new(20.0, 0.0),
+                6.0,
+                Point::zero(),
+                6.0,
+                1.0
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_sweep_moving_away() {
+        assert!(
+            sweep_circle_circle(
+                Point::new(0.0, 20.0),
+                Point::new(0.0, 1000.0),
+                6.0,
+                Point::zero(),
+                6.0,
+                1.0
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_sweep_already_overlapping() {
+        let start = Point::new(0.0, 10.0);
+        assert_eq!(
+            sweep_circle_circle(start, Point::new(0.0, -100.0), 6.0, Point::zero(), 6.0, 1.0),
+            Some((0.0, start))
+        );
+        assert!(
+            sweep_circle_circle(start, Point::new(0.0, 100.0), 6.0, Point::zero(), 6.0, 1.0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_capsule() {
+        let capsule = Body {
+            pos: Point::new(100.0, 100.0),
+            shape: Shape::Capsule {
+                a: Point::new(-20.0, 0.0),
+                b: Point::new(20.0, 10.0),
+                radius: 5.0,
+            },
+        };
+
+        assert!(capsule.contains(Point::new(100.0, 102.0)));
+        // Inside the round end, but outside the box around the segment
+        assert!(capsule.contains(Point::new(76.0, 98.0)));
+        assert!(!capsule.contains(Point::new(100.0, 112.0)));
+        assert!(capsule.extend(10.0).contains(Point::new(100.0, 112.0)));
+
+        assert_eq!(capsule.bottom(), 115.0);
+        assert_eq!(
+            capsule.normal_at(Point::new(70.0, 100.0)),
+            Point::new(-1.0, 0.0)
+        );
+        let surface = capsule.closest_surface_point(Point::new(70.0, 100.0));
+        assert!(surface.distance_to(Point::new(75.0, 100.0)) < 1e-4);
+    }
+
+    #[test]
+    fn test_rect() {
+        let rect = Body {
+            pos: Point::new(100.0, 100.0),
+            shape: Shape::Rect {
+                half_extents: Point::new(20.0, 10.0),
+                rounding: 0.0,
+            },
+        };
+
+        assert!(rect.contains(Point::new(119.0, 91.0)));
+        assert!(!rect.contains(Point::new(121.0, 100.0)));
+        assert_eq!(rect.bottom(), 110.0);
+
+        // Grown by 5, with the corners rounded off
+        let grown = rect.extend(5.0);
+        assert!(grown.contains(Point::new(124.0, 100.0)));
+        assert!(grown.contains(Point::new(123.0, 113.0)));
+        assert!(!grown.contains(Point::new(124.0, 114.0)));
+
+        assert_eq!(
+            rect.normal_at(Point::new(100.0, 80.0)),
+            Point::new(0.0, -1.0)
+        );
+        let corner = rect.normal_at(Point::new(130.0, 120.0));
+        assert!(corner.distance_to(Point::new(1.0, 1.0).normalized()) < 1e-4);
+    }
+
+    #[test]
+    fn test_sweep_circle_rect() {
+        let center = Point::new(100.0, 100.0);
+        let half = Point::new(20.0, 10.0);
+
+        // Straight down onto the top face
+        let (t, point) = sweep_circle_rect(
+            Point::new(110.0, 50.0),
+            Point::new(0.0, 100.0),
+            5.0,
+            center,
+            half,
+            0.0,
+            1.0,
+        )
+        .unwrap();
+        assert!((t - 0.35).abs() < 1e-4);
+        assert!(point.distance_to(Point::new(110.0, 85.0)) < 1e-3);
+
+        // Diagonally into the corner, which stops it a radius away from the corner itself
+        let (_, point) = sweep_circle_rect(
+            Point::new(160.0, 50.0),
+            Point::new(-100.0, 100.0),
+            5.0,
+            center,
+            half,
+            0.0,
+            1.0,
+        )
+        .unwrap();
+        let away = Point::new(1.0, -1.0).normalized() * 5.0;
+        assert!(point.distance_to(Point::new(120.0, 90.0) + away) < 1e-3);
+
+        // Passes by the corner just outside it
+        assert!(
+            sweep_circle_rect(
+                Point::new(126.0, 50.0),
+                Point::new(0.0, 100.0),
+                5.0,
+                center,
+                half,
+                0.0,
+                1.0
+            )
+            .is_none()
+        );
+
+        // Touching and moving away
+        assert!(
+            sweep_circle_rect(
+                Point::new(110.0, 85.0),
+                Point::new(0.0, -100.0),
+                5.0,
+                center,
+                half,
+                0.0,
+                1.0
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mut triangle = Body {
+            pos: Point::new(100.0, 50.0),
+            shape: Shape::Polygon {
+                points: vec![
+                    Point::new(0.0, -10.0),
+                    Point::new(20.0, 10.0),
+                    Point::new(-5.0, 10.0),
+                ],
+                rotation: 0.3,
+                rounding: 0.0,
+            },
+        };
+        let corners = triangle.vertices();
+        triangle.mirror(200.0);
+
+        assert_eq!(triangle.pos, Point::new(300.0, 50.0));
+        for (corner, mirrored) in corners.iter().zip(triangle.vertices()) {
+            assert!(mirrored.distance_to(Point::new(400.0 - corner.x, corner.y)) < 1e-3);
+        }
+        // Still solid on the inside after the winding flipped
+        assert!(triangle.contains(Point::new(300.0, 52.0)));
+        assert!(!triangle.contains(Point::new(280.0, 70.0)));
+    }
 }
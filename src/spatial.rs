@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use crate::shape::{Body, Point};
+
+// Uniform grid over the bounding boxes of a set of bodies, for finding the few that
+// could be near some area without checking all of them. Bodies are referred to by their
+// index in the slice the grid was built from, so it has to be rebuilt whenever that
+// slice changes.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    len: usize,
+}
+
+impl SpatialHash {
+    // Cells are never smaller than this, so tiny bodies don't make for a huge grid
+    const MIN_CELL_SIZE: f32 = 16.0;
+
+    pub fn new<'a>(bodies: impl Iterator<Item = &'a Body> + Clone) -> Self {
+        // Twice the largest radius means most bodies only cover a couple of cells
+        let largest = bodies
+            .clone()
+            .map(|body| body.bounding_radius())
+            .fold(0.0, f32::max);
+        Self::with_cell_size(bodies, (2.0 * largest).max(Self::MIN_CELL_SIZE))
+    }
+
+    pub fn with_cell_size<'a>(bodies: impl Iterator<Item = &'a Body>, cell_size: f32) -> Self {
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::new(),
+            len: 0,
+        };
+        for (i, body) in bodies.enumerate() {
+            let r = Point::new(body.bounding_radius(), body.bounding_radius());
+            let ((x0, y0), (x1, y1)) = (grid.cell(body.pos - r), grid.cell(body.pos + r));
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    grid.cells.entry((x, y)).or_default().push(i);
+                }
+            }
+            grid.len += 1;
+        }
+        grid
+    }
+
+    // Number of bodies the grid was built from
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // Every body whose bounding box might overlap the box between `min` and `max`, in
+    // ascending order so results don't depend on how the cells are laid out
+    pub fn query(&self, min: Point<f32>, max: Point<f32>) -> Vec<usize> {
+        let ((x0, y0), (x1, y1)) = (self.cell(min), self.cell(max));
+        let mut out = Vec::new();
+        let area = (x1 as i64 - x0 as i64 + 1).saturating_mul(y1 as i64 - y0 as i64 + 1);
+        if area > self.cells.len() as i64 {
+            // Covers more cells than there are, so just look at all of them
+            for (&(x, y), bodies) in &self.cells {
+                if (x0..=x1).contains(&x) && (y0..=y1).contains(&y) {
+                    out.extend(bodies);
+                }
+            }
+        } else {
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    if let Some(bodies) = self.cells.get(&(x, y)) {
+                        out.extend(bodies);
+                    }
+                }
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+
+    fn cell(&self, p: Point<f32>) -> (i32, i32) {
+        (
+            (p.x / self.cell_size).floor() as i32,
+            (p.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        shape::{Body, Point, Shape},
+        spatial::SpatialHash,
+    };
+
+    fn circle(x: f32, y: f32, radius: f32) -> Body {
+        Body {
+            pos: Point::new(x, y),
+            shape: Shape::Circle { radius },
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        let bodies = [
+            circle(10.0, 10.0, 6.0),
+            circle(100.0, 10.0, 6.0),
+            circle(10.0, 100.0, 6.0),
+            // Big enough to cover several cells
+            circle(300.0, 300.0, 50.0),
+        ];
+        let grid = SpatialHash::with_cell_size(bodies.iter(), 20.0);
+        assert_eq!(grid.len(), 4);
+
+        assert_eq!(
+            grid.query(Point::new(0.0, 0.0), Point::new(20.0, 20.0)),
+            vec![0]
+        );
+        assert_eq!(
+            grid.query(Point::new(0.0, 0.0), Point::new(110.0, 20.0)),
+            vec![0, 1]
+        );
+        assert_eq!(
+            grid.query(Point::new(340.0, 340.0), Point::new(345.0, 345.0)),
+            vec![3]
+        );
+        assert!(
+            grid.query(Point::new(150.0, 150.0), Point::new(160.0, 160.0))
+                .is_empty()
+        );
+        assert_eq!(
+            grid.query(Point::new(-1000.0, -1000.0), Point::new(1000.0, 1000.0)),
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_negative_coordinates() {
+        let bodies = [circle(-5.0, -5.0, 2.0)];
+        let grid = SpatialHash::new(bodies.iter());
+        assert_eq!(
+            grid.query(Point::new(-8.0, -8.0), Point::new(-4.0, -4.0)),
+            vec![0]
+        );
+        assert!(
+            grid.query(Point::new(1.0, 1.0), Point::new(8.0, 8.0))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_results_are_a_superset() {
+        let bodies: Vec<_> = (0..50)
+            .map(|i| circle((i * 37 % 400) as f32, (i * 91 % 300) as f32, 6.0))
+            .collect();
+        let grid = SpatialHash::new(bodies.iter());
+
+        let (min, max) = (Point::new(120.0, 80.0), Point::new(180.0, 130.0));
+        let found = grid.query(min, max);
+        for (i, body) in bodies.iter().enumerate() {
+            let near = body.pos.x + 6.0 >= min.x
+                && body.pos.x - 6.0 <= max.x
+                && body.pos.y + 6.0 >= min.y
+                && body.pos.y - 6.0 <= max.y;
+            if near {
+                assert!(found.contains(&i));
+            }
+        }
+    }
+}